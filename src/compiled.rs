@@ -0,0 +1,776 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::path::{parse_path, ParsedPath, PathSegment};
+use crate::{
+    as_array, as_number, join_path, nested_path, resolve_branches, resolve_parsed, values_equal,
+    Context, Filter, FilterError, NumRepr, Operator,
+};
+
+/// A [`Filter`] prepared for evaluating against many documents.
+///
+/// Compilation precomputes what can be derived from the filter alone: paths
+/// (of the filter and those nested in `And`, `Or` and `Not`) are parsed once,
+/// `In` candidate lists made up only of numbers or only of strings are sorted
+/// for an O(log n) binary search, and other lists become hash sets for an O(1)
+/// lookup, instead of an O(n) scan over the list. `Regex` and `RegexCapture`
+/// patterns are compiled once, including those nested in other operators such
+/// as `Fields`, `OptionalMatch` or `All`. Everything else is evaluated exactly
+/// like [`Filter::check`].
+#[derive(Debug, Clone)]
+pub struct CompiledFilter {
+    filter: Filter,
+    root: Node,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    path: Arc<str>,
+    /// `None` when the path is malformed, which `check` reports.
+    parsed: Option<ParsedPath>,
+    operator: CompiledOperator,
+}
+
+#[derive(Debug, Clone)]
+enum Candidates {
+    /// Sorted by numeric value.
+    Numbers(Vec<Value>),
+    /// Sorted.
+    Strings(Vec<String>),
+    /// Keyed by [`membership_key`].
+    Mixed(HashSet<String>),
+}
+
+impl Candidates {
+    fn new(candidates: &[Value]) -> Self {
+        if candidates.iter().all(Value::is_string) {
+            let mut strings: Vec<String> = candidates
+                .iter()
+                .filter_map(|candidate| candidate.as_str().map(str::to_string))
+                .collect();
+            strings.sort_unstable();
+            Candidates::Strings(strings)
+        } else if candidates.iter().all(Value::is_number) {
+            let mut numbers = candidates.to_vec();
+            numbers.sort_by(|a, b| numeric_key(a).total_cmp(&numeric_key(b)));
+            Candidates::Numbers(numbers)
+        } else {
+            Candidates::Mixed(candidates.iter().map(membership_key).collect())
+        }
+    }
+
+    fn contains(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Candidates::Strings(strings), Value::String(s)) => strings
+                .binary_search_by(|candidate| candidate.as_str().cmp(s))
+                .is_ok(),
+            (Candidates::Numbers(numbers), Value::Number(_)) => {
+                // Several candidates may share a numeric key (e.g. `5` and `5.0`),
+                // so scan the run of equal keys for an exact match
+                let key = numeric_key(value);
+                let start = numbers.partition_point(|candidate| numeric_key(candidate) < key);
+                numbers[start..]
+                    .iter()
+                    .take_while(|candidate| numeric_key(candidate) == key)
+                    .any(|candidate| values_equal(candidate, value))
+            }
+            (Candidates::Mixed(keys), value) => keys.contains(&membership_key(value)),
+            _ => false,
+        }
+    }
+}
+
+fn numeric_key(value: &Value) -> f64 {
+    value.as_f64().unwrap_or(f64::NAN)
+}
+
+#[derive(Debug, Clone)]
+enum CompiledOperator {
+    In(Candidates),
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Not(Box<Node>),
+    Interpreted(Operator),
+}
+
+impl CompiledFilter {
+    pub(crate) fn new(filter: &Filter) -> Self {
+        Self {
+            filter: filter.clone(),
+            root: Node::compile(filter, None).expect("compiling without a context can't fail"),
+        }
+    }
+
+    /// Compiles `filter` for checking documents against a shared `context`
+    /// document, such as a configuration holding allow-lists.
+    ///
+    /// The arrays referenced by `InPath` are resolved against `context` (from
+    /// its root, at any nesting level, including inside operators such as
+    /// `OptionalMatch` or `All`) once here, instead of against each checked
+    /// document, and then looked up like an `In`.
+    /// Errors resolving them are reported here as well. Reuse the result for
+    /// as long as `context` doesn't change.
+    pub fn with_context(filter: &Filter, context: &Value) -> Result<Self, FilterError> {
+        Ok(Self {
+            filter: filter.clone(),
+            root: Node::compile(filter, Some(context))?,
+        })
+    }
+
+    /// Compiles `filter`, rejecting array indices that are out of range for
+    /// arrays of known, fixed length.
+    ///
+    /// `schema` maps the path of an array, written the same way as in filters
+    /// (e.g. `"point.coords"` or `"rows[0].cells"`), to its length. Indices into
+    /// arrays missing from the schema are not checked.
+    pub fn compile_with_schema(
+        filter: &Filter,
+        schema: &HashMap<String, usize>,
+    ) -> Result<Self, FilterError> {
+        check_indices(filter, ".", schema)?;
+        Ok(Self::new(filter))
+    }
+
+    /// The filter this was compiled from.
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    pub fn check(&self, value: &Value) -> Result<bool, FilterError> {
+        self.root.check(value)
+    }
+}
+
+impl Node {
+    fn compile(filter: &Filter, context: Option<&Value>) -> Result<Self, FilterError> {
+        let compile_all = |filters: &[Filter]| {
+            filters
+                .iter()
+                .map(|filter| Node::compile(filter, context))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let operator = match (&filter.operator, context) {
+            (Operator::In(candidates), _) => CompiledOperator::In(Candidates::new(candidates)),
+            (Operator::InPath(path), Some(context)) => {
+                CompiledOperator::In(Candidates::new(&context_candidates(path, context)?))
+            }
+            (Operator::And(filters), _) => CompiledOperator::And(compile_all(filters)?),
+            (Operator::Or(filters), _) => CompiledOperator::Or(compile_all(filters)?),
+            (Operator::Not(inner), _) => {
+                CompiledOperator::Not(Box::new(Node::compile(inner, context)?))
+            }
+            (operator, _) => CompiledOperator::Interpreted(precompile(operator, context)?),
+        };
+
+        Ok(Self {
+            path: filter.path.clone(),
+            parsed: parse_path(&filter.path).ok(),
+            operator,
+        })
+    }
+
+    fn check(&self, value: &Value) -> Result<bool, FilterError> {
+        let resolved = match &self.parsed {
+            Some(parsed) => resolve_parsed(parsed, value),
+            None => resolve_branches(&self.path, value),
+        };
+        let branches = match resolved {
+            Ok(branches) => branches,
+            Err(FilterError::PathNotFound(path)) => {
+                return match &self.operator {
+                    CompiledOperator::Interpreted(operator) => operator
+                        .missing_result()
+                        .ok_or(FilterError::PathNotFound(path)),
+                    _ => Err(FilterError::PathNotFound(path)),
+                };
+            }
+            Err(error) => return Err(error),
+        };
+
+        for (_, target) in branches {
+            if self.evaluate(&target, value)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn evaluate(&self, value: &Value, scope: &Value) -> Result<bool, FilterError> {
+        match &self.operator {
+            CompiledOperator::In(candidates) => Ok(candidates.contains(value)),
+
+            CompiledOperator::And(nodes) => {
+                for node in nodes {
+                    if !node.check(value)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+
+            CompiledOperator::Or(nodes) => {
+                for node in nodes {
+                    if node.check(value)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+
+            CompiledOperator::Not(node) => Ok(!node.check(value)?),
+
+            CompiledOperator::Interpreted(operator) => {
+                operator.evaluate(value, scope, &Context::default())
+            }
+        }
+    }
+}
+
+fn check_indices(
+    filter: &Filter,
+    parent: &str,
+    schema: &HashMap<String, usize>,
+) -> Result<(), FilterError> {
+    let path = nested_path(parent, &filter.path);
+
+    let mut prefix = String::new();
+    for segment in parse_path(&path)?.segments {
+        prefix = match segment {
+            PathSegment::Index(index) => {
+                if schema.get(&prefix).is_some_and(|&len| index >= len) {
+                    return Err(FilterError::InvalidArrayIndex(format!(
+                        "{}[{}]",
+                        prefix, index
+                    )));
+                }
+                format!("{}[{}]", prefix, index)
+            }
+            PathSegment::IndexFromEnd(index) => match schema.get(&prefix) {
+                Some(&len) if index > len => {
+                    return Err(FilterError::InvalidArrayIndex(format!(
+                        "{}[-{}]",
+                        prefix, index
+                    )));
+                }
+                Some(&len) => format!("{}[{}]", prefix, len - index),
+                None => format!("{}{}", prefix, segment),
+            },
+            PathSegment::Where { .. } => format!("{}{}", prefix, segment),
+            segment => join_path(&prefix, &segment.to_string()),
+        };
+    }
+
+    for filter in filter.operator.subfilters() {
+        check_indices(filter, &path, schema)?;
+    }
+    Ok(())
+}
+
+/// Canonical string form of a value: two values are equal as in `Equals`
+/// exactly when their keys are equal. Object keys serialize sorted, and numbers
+/// at any depth are normalized first, so `1`, `1.0` and `[1.0]`/`[1]` agree.
+fn membership_key(value: &Value) -> String {
+    normalize_numbers(value).to_string()
+}
+
+fn normalize_numbers(value: &Value) -> Value {
+    match value {
+        Value::Number(_) => match as_number(value) {
+            Some(NumRepr::Int(i)) => Value::from(i),
+            Some(NumRepr::UInt(u)) => Value::from(u),
+            Some(NumRepr::Float(f)) => Value::from(f),
+            None => value.clone(),
+        },
+        Value::Array(arr) => Value::Array(arr.iter().map(normalize_numbers).collect()),
+        Value::Object(obj) => Value::Object(
+            obj.iter()
+                .map(|(key, value)| (key.clone(), normalize_numbers(value)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+/// The elements of the arrays `path` resolves to in `context`, which
+/// `InPath` is checked against.
+fn context_candidates(path: &str, context: &Value) -> Result<Vec<Value>, FilterError> {
+    let mut candidates = Vec::new();
+    for (_, list) in resolve_branches(path, context)? {
+        candidates.extend_from_slice(as_array(&list)?);
+    }
+    Ok(candidates)
+}
+
+/// `operator` with the regex patterns in it and in the operators and filters
+/// nested in it compiled once up front, and with a `context`, their `InPath`
+/// resolved against it into an `In`. Invalid patterns are left to fail with
+/// `InvalidRegex` when checked.
+fn precompile(operator: &Operator, context: Option<&Value>) -> Result<Operator, FilterError> {
+    let boxed = |operator: &Operator| precompile(operator, context).map(Box::new);
+    let filter = |filter: &Filter| {
+        Ok(Filter {
+            path: filter.path.clone(),
+            operator: precompile(&filter.operator, context)?,
+        })
+    };
+    let filters = |filters: &[Filter]| filters.iter().map(filter).collect::<Result<_, _>>();
+
+    Ok(match operator {
+        Operator::InPath(path) => match context {
+            Some(context) => Operator::In(context_candidates(path, context)?),
+            None => operator.clone(),
+        },
+        #[cfg(feature = "regex")]
+        Operator::Regex(pattern) => match regex::Regex::new(pattern) {
+            Ok(regex) => Operator::MatchesRegex(crate::CompiledRegex(regex)),
+            Err(_) => operator.clone(),
+        },
+        #[cfg(feature = "regex")]
+        Operator::RegexCapture {
+            pattern,
+            group,
+            inner,
+        } => match regex::Regex::new(pattern) {
+            Ok(regex) => Operator::MatchesRegexCapture {
+                regex: crate::CompiledRegex(regex),
+                group: group.clone(),
+                inner: boxed(inner)?,
+            },
+            Err(_) => operator.clone(),
+        },
+        #[cfg(feature = "regex")]
+        Operator::MatchesRegexCapture {
+            regex,
+            group,
+            inner,
+        } => Operator::MatchesRegexCapture {
+            regex: regex.clone(),
+            group: group.clone(),
+            inner: boxed(inner)?,
+        },
+        Operator::Length(inner) => Operator::Length(boxed(inner)?),
+        Operator::Flatten { depth, inner } => Operator::Flatten {
+            depth: *depth,
+            inner: boxed(inner)?,
+        },
+        Operator::OptionalMatch(inner) => Operator::OptionalMatch(boxed(inner)?),
+        Operator::OrFalseOnMismatch(inner) => Operator::OrFalseOnMismatch(boxed(inner)?),
+        Operator::Fields {
+            fields,
+            missing_is_error,
+        } => Operator::Fields {
+            fields: fields
+                .iter()
+                .map(|(key, operator)| Ok((key.clone(), precompile(operator, context)?)))
+                .collect::<Result<_, FilterError>>()?,
+            missing_is_error: *missing_is_error,
+        },
+        Operator::Window {
+            size,
+            inner,
+            quantifier,
+        } => Operator::Window {
+            size: *size,
+            inner: Box::new(filter(inner)?),
+            quantifier: *quantifier,
+        },
+        Operator::All(inner) => Operator::All(Box::new(filter(inner)?)),
+        Operator::Any(inner) => Operator::Any(Box::new(filter(inner)?)),
+        Operator::EntriesMatching {
+            key_glob,
+            inner,
+            quantifier,
+        } => Operator::EntriesMatching {
+            key_glob: key_glob.clone(),
+            inner: Box::new(filter(inner)?),
+            quantifier: *quantifier,
+        },
+        Operator::And(inner) => Operator::And(filters(inner)?),
+        Operator::Or(inner) => Operator::Or(filters(inner)?),
+        Operator::Not(inner) => Operator::Not(Box::new(filter(inner)?)),
+        operator => operator.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_compiled_matches_interpreted() {
+        let documents = [
+            json!({
+                "age": 25,
+                "name": "John Doe",
+                "tags": ["rust", "json"],
+                "user": { "id": 7, "roles": ["admin"], "email": "john@example.com" },
+                "orders": [{ "total": 10, "status": "open" }, { "total": 0, "status": "done" }],
+                "matrix": [[1, 2], [3, 4]],
+                "config": { "server.port": 8080 }
+            }),
+            json!({ "age": "25", "tags": "rust", "orders": [], "matrix": [[1]] }),
+            json!([1, 2, 3]),
+            json!(null),
+        ];
+        let filters = [
+            Filter::new("age", Operator::GreaterThan(20.0)),
+            Filter::new("name", Operator::StartsWith("John".to_string())),
+            Filter::new("tags", Operator::ArrayContains(json!("rust"))),
+            Filter::new("tags[-1]", Operator::Equals(json!("json"))),
+            Filter::new(
+                "user.roles[0]",
+                Operator::In(vec![json!("admin"), json!(1)]),
+            ),
+            Filter::new("orders.*.total", Operator::GreaterThan(5.0)),
+            Filter::new("orders[?status=open].total", Operator::Equals(json!(10))),
+            Filter::new(
+                r#"orders["total">5].status"#,
+                Operator::Equals(json!("open")),
+            ),
+            Filter::new("orders[].status", Operator::Equals(json!("done"))),
+            Filter::new("orders#length", Operator::Equals(json!(2))),
+            Filter::new("matrix[1][0]", Operator::Equals(json!(3))),
+            Filter::new(r#"config["server.port"]"#, Operator::Equals(json!(8080))),
+            Filter::new("[1]", Operator::Equals(json!(2))),
+            Filter::new(
+                "user.nickname",
+                Operator::OptionalMatch(Box::new(Operator::Luhn)),
+            ),
+            Filter::new("user.email", Operator::Exists(false)),
+            Filter::new("a..b", Operator::Exists(true)),
+            Filter::new(
+                "user",
+                Operator::And(vec![
+                    Filter::new("id", Operator::LessThan(10.0)),
+                    Filter::new(
+                        ".",
+                        Operator::Or(vec![
+                            Filter::new("email", Operator::EndsWith(".org".to_string())),
+                            Filter::new(
+                                ".",
+                                Operator::Not(Box::new(Filter::new(
+                                    "roles",
+                                    Operator::ArrayContains(json!("guest")),
+                                ))),
+                            ),
+                        ]),
+                    ),
+                ]),
+            ),
+            Filter::new(
+                "orders",
+                Operator::All(Box::new(Filter::new("total", Operator::LessThan(100.0)))),
+            ),
+        ];
+
+        for filter in &filters {
+            let compiled = filter.compile();
+            for document in &documents {
+                let interpreted = filter.check(document).map_err(|error| error.to_string());
+                let compiled = compiled.check(document).map_err(|error| error.to_string());
+                assert_eq!(compiled, interpreted, "{:?} on {}", filter, document);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compiled_in() {
+        let candidates: Vec<Value> = (0..10_000).map(|i| json!(i * 2)).collect();
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("id", Operator::In(candidates)),
+                Filter::new(
+                    "status",
+                    Operator::In(vec![json!("active"), json!("pending")]),
+                ),
+            ]),
+        );
+        let compiled = filter.compile();
+
+        for (value, expected) in [
+            (json!({ "id": 4242, "status": "active" }), true),
+            (json!({ "id": 4243, "status": "active" }), false),
+            (json!({ "id": 4242, "status": "deleted" }), false),
+            (json!({ "id": "4242", "status": "pending" }), false),
+        ] {
+            assert_eq!(filter.check(&value).unwrap(), expected);
+            assert_eq!(compiled.check(&value).unwrap(), expected);
+        }
+
+        let value = json!({ "status": "active" });
+        assert!(matches!(
+            compiled.check(&value),
+            Err(FilterError::PathNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_compiled_in_candidate_kinds() {
+        let cases = [
+            vec![
+                json!(3),
+                json!(-1.5),
+                json!(10),
+                json!(2.0),
+                json!(u64::MAX),
+            ],
+            vec![json!("pear"), json!("apple"), json!("fig")],
+            vec![
+                json!("apple"),
+                json!(3),
+                json!(null),
+                json!({ "a": [1] }),
+                json!(2.0),
+                json!([1.0, -0.0]),
+            ],
+            vec![],
+        ];
+        let probes = [
+            json!(3),
+            json!(2.0),
+            json!(2),
+            json!(-1.5),
+            json!(u64::MAX),
+            json!(u64::MAX - 1),
+            json!("apple"),
+            json!("fig"),
+            json!("grape"),
+            json!(null),
+            json!({ "a": [1] }),
+            json!({ "a": [1.0] }),
+            json!([1, 0]),
+            json!(3.0),
+            json!(true),
+        ];
+
+        for candidates in cases {
+            let filter = Filter::new("x", Operator::In(candidates.clone()));
+            let compiled = filter.compile();
+            for probe in &probes {
+                let value = json!({ "x": probe });
+                assert_eq!(
+                    compiled.check(&value).unwrap(),
+                    filter.check(&value).unwrap(),
+                    "{:?} in {:?}",
+                    probe,
+                    candidates
+                );
+            }
+        }
+
+        assert!(matches!(
+            Candidates::new(&[json!(1), json!(2)]),
+            Candidates::Numbers(..)
+        ));
+        assert!(matches!(
+            Candidates::new(&[json!("a"), json!("b")]),
+            Candidates::Strings(..)
+        ));
+        assert!(matches!(
+            Candidates::new(&[json!("a"), json!(1)]),
+            Candidates::Mixed(..)
+        ));
+    }
+
+    #[test]
+    fn test_compile_with_schema() {
+        let schema = HashMap::from([
+            ("point.coords".to_string(), 3),
+            ("rows[0].cells".to_string(), 2),
+        ]);
+
+        let filter = Filter::new(
+            "point",
+            Operator::And(vec![
+                Filter::new("coords[2]", Operator::GreaterThan(0.0)),
+                Filter::new("tags[10]", Operator::Equals(json!("x"))),
+            ]),
+        );
+        let compiled = CompiledFilter::compile_with_schema(&filter, &schema).unwrap();
+        let value = json!({ "point": { "coords": [1, 2, 3], "tags": [] } });
+        assert!(matches!(
+            compiled.check(&value),
+            Err(FilterError::InvalidArrayIndex(..))
+        ));
+
+        let filter = Filter::new(
+            "point",
+            Operator::Or(vec![Filter::new("coords[3]", Operator::GreaterThan(0.0))]),
+        );
+        assert!(matches!(
+            CompiledFilter::compile_with_schema(&filter, &schema),
+            Err(FilterError::InvalidArrayIndex(index)) if index == "point.coords[3]"
+        ));
+
+        let filter = Filter::new("rows[0].cells[2]", Operator::Equals(json!(1)));
+        assert!(CompiledFilter::compile_with_schema(&filter, &schema).is_err());
+        let filter = Filter::new("rows[0].cells[1]", Operator::Equals(json!(1)));
+        assert!(CompiledFilter::compile_with_schema(&filter, &schema).is_ok());
+
+        let filter = Filter::new("point.coords[-3]", Operator::Equals(json!(1)));
+        assert!(CompiledFilter::compile_with_schema(&filter, &schema).is_ok());
+        let filter = Filter::new("point.coords[-4]", Operator::Equals(json!(1)));
+        assert!(matches!(
+            CompiledFilter::compile_with_schema(&filter, &schema),
+            Err(FilterError::InvalidArrayIndex(index)) if index == "point.coords[-4]"
+        ));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_compiled_nested_regex() {
+        let filter = Filter::new(
+            "user",
+            Operator::And(vec![
+                Filter::new(
+                    "email",
+                    Operator::OptionalMatch(Box::new(Operator::Regex(
+                        r"@example\.com$".to_string(),
+                    ))),
+                ),
+                Filter::new(
+                    ".",
+                    Operator::Fields {
+                        fields: [(
+                            "name".to_string(),
+                            Operator::RegexCapture {
+                                pattern: r"^(\w+)".to_string(),
+                                group: crate::CaptureGroup::Index(1),
+                                inner: Box::new(Operator::Equals(json!("John"))),
+                            },
+                        )]
+                        .into(),
+                        missing_is_error: false,
+                    },
+                ),
+                Filter::new(
+                    "tags",
+                    Operator::Any(Box::new(Filter::new(
+                        ".",
+                        Operator::Regex("^ru".to_string()),
+                    ))),
+                ),
+            ]),
+        );
+        let compiled = filter.compile();
+
+        // Every pattern is compiled up front, at any depth
+        let CompiledOperator::And(nodes) = &compiled.root.operator else {
+            panic!("{:?}", compiled.root.operator);
+        };
+        assert!(matches!(
+            &nodes[0].operator,
+            CompiledOperator::Interpreted(Operator::OptionalMatch(inner))
+                if matches!(**inner, Operator::MatchesRegex(_))
+        ));
+        assert!(matches!(
+            &nodes[1].operator,
+            CompiledOperator::Interpreted(Operator::Fields { fields, .. })
+                if matches!(fields["name"], Operator::MatchesRegexCapture { .. })
+        ));
+        assert!(matches!(
+            &nodes[2].operator,
+            CompiledOperator::Interpreted(Operator::Any(inner))
+                if matches!(inner.operator, Operator::MatchesRegex(_))
+        ));
+
+        for document in [
+            json!({ "user": { "email": "john@example.com", "name": "John Doe", "tags": ["rust"] } }),
+            json!({ "user": { "name": "John Doe", "tags": ["rust"] } }),
+            json!({ "user": { "email": "john@example.org", "name": "John Doe", "tags": ["rust"] } }),
+            json!({ "user": { "name": "Jane Doe", "tags": ["rust"] } }),
+            json!({ "user": { "name": "John Doe", "tags": ["go"] } }),
+            json!({ "user": { "name": 7, "tags": ["rust"] } }),
+        ] {
+            let interpreted = filter.check(&document).map_err(|error| error.to_string());
+            let compiled = compiled.check(&document).map_err(|error| error.to_string());
+            assert_eq!(compiled, interpreted, "{}", document);
+        }
+
+        // Invalid patterns still fail when checked
+        let filter = Filter::new(
+            "name",
+            Operator::OptionalMatch(Box::new(Operator::Regex("(".to_string()))),
+        );
+        assert!(matches!(
+            filter.compile().check(&json!({ "name": "John" })),
+            Err(FilterError::InvalidRegex(..))
+        ));
+    }
+
+    #[test]
+    fn test_with_context() {
+        let config = json!({ "allowed": { "roles": ["admin", "editor"], "regions": ["eu"] } });
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("role", Operator::InPath("allowed.roles".to_string())),
+                Filter::new("region", Operator::InPath("allowed.regions".to_string())),
+            ]),
+        );
+        let compiled = CompiledFilter::with_context(&filter, &config).unwrap();
+
+        // Same result as checking a document that embeds the config
+        for (value, expected) in [
+            (json!({ "role": "editor", "region": "eu" }), true),
+            (json!({ "role": "guest", "region": "eu" }), false),
+            (json!({ "role": "admin", "region": "us" }), false),
+        ] {
+            assert_eq!(compiled.check(&value).unwrap(), expected);
+            let mut embedded = value.clone();
+            embedded["allowed"] = config["allowed"].clone();
+            assert_eq!(filter.check(&embedded).unwrap(), expected);
+        }
+
+        // Nested in other operators too
+        let filter = Filter::new(
+            "role",
+            Operator::OptionalMatch(Box::new(Operator::InPath("allowed.roles".to_string()))),
+        );
+        let compiled = CompiledFilter::with_context(&filter, &config).unwrap();
+        assert!(compiled.check(&json!({ "role": "admin" })).unwrap());
+        assert!(!compiled.check(&json!({ "role": "guest" })).unwrap());
+        assert!(compiled.check(&json!({})).unwrap());
+
+        let filter = Filter::new(
+            "users",
+            Operator::All(Box::new(Filter::new(
+                "role",
+                Operator::InPath("allowed.roles".to_string()),
+            ))),
+        );
+        let compiled = CompiledFilter::with_context(&filter, &config).unwrap();
+        let value = json!({ "users": [{ "role": "admin" }, { "role": "editor" }] });
+        assert!(compiled.check(&value).unwrap());
+        let value = json!({ "users": [{ "role": "admin" }, { "role": "guest" }] });
+        assert!(!compiled.check(&value).unwrap());
+
+        let filter = Filter::new(
+            "users",
+            Operator::Any(Box::new(Filter::new(
+                "role",
+                Operator::InPath("allowed.missing".to_string()),
+            ))),
+        );
+        assert!(matches!(
+            CompiledFilter::with_context(&filter, &config),
+            Err(FilterError::PathNotFound(..))
+        ));
+
+        let filter = Filter::new("role", Operator::InPath("allowed.missing".to_string()));
+        assert!(matches!(
+            CompiledFilter::with_context(&filter, &config),
+            Err(FilterError::PathNotFound(..))
+        ));
+        let filter = Filter::new("role", Operator::InPath("allowed".to_string()));
+        assert!(matches!(
+            CompiledFilter::with_context(&filter, &config),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,469 @@
+//! Plain English descriptions of filters, for readers who don't know the
+//! filter format, e.g. "age is greater than 20 and name starts with 'John'".
+
+use serde_json::Value;
+
+use crate::{
+    nested_path, CompareOp, Filter, FormatKind, JsonType, Operator, Quantifier, Reducer, Sign,
+};
+
+impl Filter {
+    /// Describes the filter in plain English. Leaf conditions read as "path
+    /// predicate", with paths of nested filters joined onto their parents';
+    /// `And` and `Or` become "and" / "or" lists, parenthesized when nested in
+    /// one another. The wording may change between versions, so it's meant for
+    /// display rather than parsing.
+    pub fn describe(&self) -> String {
+        describe_filter(self, ".", "the value")
+    }
+}
+
+/// Describes `filter` whose path is relative to `parent`, calling the value at
+/// `.` `itself`.
+fn describe_filter(filter: &Filter, parent: &str, itself: &str) -> String {
+    let path = nested_path(parent, &filter.path);
+    match &filter.operator {
+        Operator::And(filters) if filters.is_empty() => "always true".to_string(),
+        Operator::Or(filters) if filters.is_empty() => "never true".to_string(),
+        Operator::And(filters) | Operator::Or(filters) => {
+            let mut grouped = false;
+            let clauses: Vec<_> = filters
+                .iter()
+                .map(|child| {
+                    let clause = describe_filter(child, &path, itself);
+                    match &child.operator {
+                        Operator::And(nested) | Operator::Or(nested) if nested.len() > 1 => {
+                            grouped = true;
+                            format!("({})", clause)
+                        }
+                        _ => clause,
+                    }
+                })
+                .collect();
+            let conjunction = match filter.operator {
+                Operator::And(_) => "and",
+                _ => "or",
+            };
+            // Commas next to a parenthesized list would read as part of it
+            if grouped {
+                clauses.join(&format!(" {} ", conjunction))
+            } else {
+                join_list(clauses, conjunction)
+            }
+        }
+        Operator::Not(inner) => format!("not ({})", describe_filter(inner, &path, itself)),
+        operator => {
+            let subject = if path == "." { itself } else { &path };
+            format!("{} {}", subject, predicate(operator))
+        }
+    }
+}
+
+/// What the operator requires of the value, phrased to follow its path.
+fn predicate(operator: &Operator) -> String {
+    match operator {
+        Operator::GreaterThan(n) => format!("is greater than {}", n),
+        Operator::LessThan(n) => format!("is less than {}", n),
+        Operator::GreaterOrEqual(n) => format!("is at least {}", n),
+        Operator::LessOrEqual(n) => format!("is at most {}", n),
+        Operator::UlpEquals { target, max_ulps } => {
+            format!("is within {} ULPs of {}", max_ulps, target)
+        }
+        Operator::Sign(sign) => match sign {
+            Sign::Positive => "is positive",
+            Sign::Negative => "is negative",
+            Sign::Zero => "is zero",
+            Sign::NonNegative => "is not negative",
+            Sign::NonPositive => "is not positive",
+        }
+        .to_string(),
+        Operator::GreaterThanScaled { value, scale } => {
+            format!("divided by {} is greater than {}", scale, value)
+        }
+        Operator::MultipleOfPath(path) => format!("is a multiple of {}", path),
+        Operator::Range {
+            min,
+            max,
+            min_inclusive,
+            max_inclusive,
+        } => {
+            let mut bounds = Vec::new();
+            if let Some(min) = min {
+                bounds.push(if *min_inclusive {
+                    format!("is at least {}", min)
+                } else {
+                    format!("is greater than {}", min)
+                });
+            }
+            if let Some(max) = max {
+                bounds.push(if *max_inclusive {
+                    format!("is at most {}", max)
+                } else {
+                    format!("is less than {}", max)
+                });
+            }
+            if bounds.is_empty() {
+                "is a number".to_string()
+            } else {
+                join_list(bounds, "and")
+            }
+        }
+        Operator::Between {
+            min,
+            max,
+            inclusive,
+        } => format!(
+            "is between {} and {} ({})",
+            min,
+            max,
+            if *inclusive { "inclusive" } else { "exclusive" }
+        ),
+        Operator::InRanges(ranges) => format!(
+            "is in the range {}",
+            join_list(
+                ranges
+                    .iter()
+                    .map(|(min, max)| format!("{} to {}", min, max))
+                    .collect(),
+                "or"
+            )
+        ),
+        Operator::RoundsTo { value, decimals } => {
+            format!("rounds to {} at {} decimal places", value, decimals)
+        }
+        Operator::AggregateEqualsPath {
+            array_path,
+            reducer,
+            field,
+            other_path,
+        } => {
+            let reducer = match reducer {
+                Reducer::Sum => "sum",
+                Reducer::Min => "minimum",
+                Reducer::Max => "maximum",
+                Reducer::Average => "average",
+                Reducer::Count => "count",
+            };
+            format!(
+                "has a {} of {} in {} equal to {}",
+                reducer, field, array_path, other_path
+            )
+        }
+        #[cfg(feature = "byte-size")]
+        Operator::SizeGreaterThan(size) => format!("is larger than {}", size),
+        #[cfg(feature = "geo")]
+        Operator::WithinDistance {
+            lat,
+            lng,
+            km,
+            lat_path,
+            lng_path,
+        } => format!(
+            "has {} and {} within {} km of ({}, {})",
+            lat_path, lng_path, km, lat, lng
+        ),
+        #[cfg(feature = "datetime")]
+        Operator::WithinDurationOf {
+            other_path,
+            duration_secs,
+        } => format!("is within {} seconds of {}", duration_secs, other_path),
+
+        Operator::Equals(value) => format!("is {}", describe_value(value)),
+        Operator::NotEqual(value) => format!("is not {}", describe_value(value)),
+        Operator::In(values) => format!("is {}", describe_values(values, "or")),
+        Operator::DeepEquals {
+            target,
+            ignore_array_order,
+        } => {
+            if *ignore_array_order {
+                format!("is {} in any array order", target)
+            } else {
+                format!("is {}", target)
+            }
+        }
+        Operator::InPath(path) => {
+            format!("is one of the values in {}", path)
+        }
+        Operator::SameAs(path) => format!("is the same as {}", path),
+
+        Operator::StartsWith(s) => format!("starts with {}", quote(s)),
+        Operator::EndsWith(s) => format!("ends with {}", quote(s)),
+        Operator::Contains(s) => format!("contains {}", quote(s)),
+        Operator::EqualsIgnoreCase(s) => format!("is {} ignoring case", quote(s)),
+        Operator::StartsWithIgnoreCase(s) => format!("starts with {} ignoring case", quote(s)),
+        Operator::EndsWithIgnoreCase(s) => format!("ends with {} ignoring case", quote(s)),
+        Operator::ContainsIgnoreCase(s) => format!("contains {} ignoring case", quote(s)),
+        Operator::EqualsTrimmed(s) => format!("is {} ignoring surrounding whitespace", quote(s)),
+        Operator::EqualsNormalizedWhitespace(s) => {
+            format!("is {} ignoring extra whitespace", quote(s))
+        }
+        Operator::Format(kind) => match kind {
+            FormatKind::Uuid => "is a UUID",
+            FormatKind::Email => "is an email address",
+            FormatKind::Url => "is a URL",
+            FormatKind::Ipv4 => "is an IPv4 address",
+            FormatKind::Ipv6 => "is an IPv6 address",
+        }
+        .to_string(),
+        Operator::LooksLikeNumber => "looks like a number".to_string(),
+        Operator::LooksLikeInteger => "looks like an integer".to_string(),
+        Operator::Luhn => "passes the Luhn check".to_string(),
+        Operator::FuzzyMatches {
+            target,
+            min_similarity,
+        } => format!(
+            "is at least {}% similar to {}",
+            min_similarity * 100.0,
+            quote(target)
+        ),
+        #[cfg(feature = "phonetic")]
+        Operator::Phonetic { target, .. } => format!("sounds like {}", quote(target)),
+        #[cfg(feature = "diacritics")]
+        Operator::ContainsNormalized(s) => format!("contains {} ignoring accents", quote(s)),
+        #[cfg(feature = "collation")]
+        Operator::CollatedEquals { target, locale } => {
+            format!("is {} in {} collation", quote(target), locale)
+        }
+        #[cfg(feature = "hash")]
+        Operator::HashEquals {
+            algorithm,
+            expected,
+        } => format!("has the {:?} hash {}", algorithm, expected),
+        #[cfg(feature = "regex")]
+        Operator::Regex(pattern) => format!("matches /{}/", pattern),
+        #[cfg(feature = "regex")]
+        Operator::RegexCapture {
+            pattern,
+            group,
+            inner,
+        } => format!(
+            "matches /{}/ with group {} that {}",
+            pattern,
+            group,
+            predicate(inner)
+        ),
+        #[cfg(feature = "regex")]
+        Operator::MatchesRegex(regex) => format!("matches /{}/", regex.0.as_str()),
+        #[cfg(feature = "regex")]
+        Operator::MatchesRegexCapture {
+            regex,
+            group,
+            inner,
+        } => format!(
+            "matches /{}/ with group {} that {}",
+            regex.0.as_str(),
+            group,
+            predicate(inner)
+        ),
+
+        Operator::ArrayContains(value) => format!("contains {}", describe_value(value)),
+        Operator::ArrayContainsAll(values) => {
+            format!("contains {}", describe_values(values, "and"))
+        }
+        Operator::ArrayContainsAny(values) => {
+            format!("contains {}", describe_values(values, "or"))
+        }
+        Operator::IsSubsetOf(values) => {
+            format!("only contains {}", describe_values(values, "or"))
+        }
+        Operator::DuplicateCount { op, count } => {
+            let op = match op {
+                CompareOp::Eq => "exactly",
+                CompareOp::Ne => "other than",
+                CompareOp::Gt => "more than",
+                CompareOp::Ge => "at least",
+                CompareOp::Lt => "fewer than",
+                CompareOp::Le => "at most",
+            };
+            format!("has {} {} duplicates", op, count)
+        }
+        Operator::ArrayEqualsIgnoringIndices { target, ignore } => format!(
+            "is {} except at positions {}",
+            Value::from(target.clone()),
+            join_list(ignore.iter().map(usize::to_string).collect(), "and")
+        ),
+        Operator::Length(inner) => format!("has a length that {}", predicate(inner)),
+        Operator::Flatten { depth, inner } => {
+            format!("flattened {} levels deep {}", depth, predicate(inner))
+        }
+        Operator::Window {
+            size,
+            inner,
+            quantifier,
+        } => format!(
+            "has {} run of {} elements where {}",
+            quantifier_word(*quantifier),
+            size,
+            describe_filter(inner, ".", "it")
+        ),
+        Operator::All(inner) => format!(
+            "has only elements where {}",
+            describe_filter(inner, ".", "it")
+        ),
+        Operator::Any(inner) => {
+            format!("has an element where {}", describe_filter(inner, ".", "it"))
+        }
+
+        Operator::HasKey(key) => format!("has the key {}", quote(key)),
+        Operator::Exists(true) => "exists".to_string(),
+        Operator::Exists(false) => "does not exist".to_string(),
+        Operator::EntriesMatching {
+            key_glob,
+            inner,
+            quantifier,
+        } => format!(
+            "has {} entry with a key like {} where {}",
+            quantifier_word(*quantifier),
+            quote(key_glob),
+            describe_filter(inner, ".", "it")
+        ),
+        Operator::Fields { fields, .. } => join_list(
+            fields
+                .iter()
+                .map(|(field, operator)| format!("has {} that {}", field, predicate(operator)))
+                .collect(),
+            "and",
+        ),
+
+        Operator::TypeIs(json_type) => match json_type {
+            JsonType::Null => "is null",
+            JsonType::Bool => "is a boolean",
+            JsonType::Number => "is a number",
+            JsonType::String => "is a string",
+            JsonType::Array => "is an array",
+            JsonType::Object => "is an object",
+        }
+        .to_string(),
+
+        // Only reached when nested in another operator
+        Operator::And(_) | Operator::Or(_) | Operator::Not(_) => {
+            format!("matches {}", operator.name())
+        }
+
+        Operator::OptionalMatch(inner) => format!("{} if present", predicate(inner)),
+        Operator::OrFalseOnMismatch(inner) => predicate(inner),
+    }
+}
+
+fn quantifier_word(quantifier: Quantifier) -> &'static str {
+    match quantifier {
+        Quantifier::Any => "some",
+        Quantifier::All => "every",
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s)
+}
+
+fn describe_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => quote(s),
+        value => value.to_string(),
+    }
+}
+
+/// `values` as a list such as `'a', 'b' or 'c'`, or `nothing` when empty.
+fn describe_values(values: &[Value], conjunction: &str) -> String {
+    if values.is_empty() {
+        "nothing".to_string()
+    } else {
+        join_list(values.iter().map(describe_value).collect(), conjunction)
+    }
+}
+
+/// Joins items as `a`, `a and b` or `a, b and c`.
+fn join_list(mut items: Vec<String>, conjunction: &str) -> String {
+    match items.pop() {
+        None => String::new(),
+        Some(last) if items.is_empty() => last,
+        Some(last) => format!("{} {} {}", items.join(", "), conjunction, last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_describe() {
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(20.0)),
+                Filter::new("name", Operator::StartsWith("John".to_string())),
+            ]),
+        );
+        assert_eq!(
+            filter.describe(),
+            "age is greater than 20 and name starts with 'John'"
+        );
+
+        let filter = Filter::new(
+            ".",
+            Operator::Or(vec![
+                Filter::new("role", Operator::Equals(json!("admin"))),
+                Filter::new(
+                    "user",
+                    Operator::And(vec![
+                        Filter::new("age", Operator::GreaterOrEqual(18.5)),
+                        Filter::new("tags", Operator::ArrayContains(json!("staff"))),
+                        Filter::new(".", Operator::HasKey("id".to_string())),
+                    ]),
+                ),
+                Filter::new(
+                    ".",
+                    Operator::Not(Box::new(Filter::new(
+                        "status",
+                        Operator::In(vec![json!("banned"), json!(0)]),
+                    ))),
+                ),
+            ]),
+        );
+        assert_eq!(
+            filter.describe(),
+            "role is 'admin' or (user.age is at least 18.5, user.tags contains 'staff' \
+             and user has the key 'id') or not (status is 'banned' or 0)"
+        );
+    }
+
+    #[test]
+    fn test_describe_nested_filters() {
+        let filter = Filter::new(
+            "orders",
+            Operator::All(Box::new(Filter::new(
+                "total",
+                Operator::Range {
+                    min: Some(0.0),
+                    max: Some(100.0),
+                    min_inclusive: false,
+                    max_inclusive: true,
+                },
+            ))),
+        );
+        assert_eq!(
+            filter.describe(),
+            "orders has only elements where total is greater than 0 and is at most 100"
+        );
+
+        let filter = Filter::new(
+            "scores",
+            Operator::Any(Box::new(Filter::new(".", Operator::LessThan(0.0)))),
+        );
+        assert_eq!(
+            filter.describe(),
+            "scores has an element where it is less than 0"
+        );
+
+        let filter = Filter::new(".", Operator::Length(Box::new(Operator::LessThan(3.0))));
+        assert_eq!(
+            filter.describe(),
+            "the value has a length that is less than 3"
+        );
+        assert_eq!(
+            Filter::new(".", Operator::And(vec![])).describe(),
+            "always true"
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! Filters addressed with paths in the syntax of the Go `gjson` library.
+//!
+//! Supported: dotted keys (with `\` escapes), numeric components as array
+//! indices, `*`, `#` (the length of an array when last, every element
+//! otherwise) and `#(key op value)` / `#(key op value)#` queries with `==`,
+//! `!=`, `<`, `<=`, `>` and `>=`. The path is translated into an equivalent
+//! native one, so `friends.#(age>45)#.first` becomes
+//! `friends[?"age">45].first`.
+//! Anything else (modifiers, pipes, glob keys, nested or pattern queries) is
+//! reported as [`FilterError::Unsupported`].
+//!
+//! Unlike in `gjson`, a numeric component is always an array index, so on an
+//! object it's a `TypeMismatch` rather than a key lookup. Escape a digit to
+//! address a numeric key, e.g. `codes.\200` for the `"200"` key of `codes`.
+
+use serde_json::Value;
+
+use crate::path::{quote_key, PathSegment};
+use crate::{CompareOp, Filter, FilterError, Operator};
+
+impl Filter {
+    /// Creates a filter whose `path` is written in `gjson` syntax, such as
+    /// `friends.#(last=="Murphy").first` or `children.#`. The stored path is
+    /// its native equivalent.
+    pub fn from_gjson(path: &str, operator: Operator) -> Result<Filter, FilterError> {
+        Filter::try_new(gjson_to_path(path)?, operator)
+    }
+}
+
+fn gjson_to_path(gjson: &str) -> Result<String, FilterError> {
+    let components = split_components(gjson)?;
+
+    let mut path = String::new();
+    for (position, component) in components.iter().enumerate() {
+        let last = position + 1 == components.len();
+        let segment = match component.as_str() {
+            "#" if last => "#length".to_string(),
+            "#" => "[]".to_string(),
+            "*" => "*".to_string(),
+            query if query.starts_with("#(") => parse_query(query)?,
+            index if index.bytes().all(|b| b.is_ascii_digit()) => format!("[{}]", index),
+            key if key.starts_with('@') || has_unescaped(key, &['*', '?']) => {
+                return Err(FilterError::Unsupported(format!("gjson component {}", key)))
+            }
+            key => quote_key(&unescape(key)).into_owned(),
+        };
+
+        if !path.is_empty() && !segment.starts_with(['[', '#']) {
+            path.push('.');
+        }
+        path.push_str(&segment);
+    }
+    Ok(path)
+}
+
+/// Splits a path on the dots outside of escapes and query parentheses,
+/// keeping each component as written.
+fn split_components(gjson: &str) -> Result<Vec<String>, FilterError> {
+    let invalid = || FilterError::InvalidPath(gjson.to_string());
+
+    let mut components = vec![String::new()];
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut chars = gjson.chars();
+    while let Some(c) = chars.next() {
+        let current = components.last_mut().expect("never empty");
+        match c {
+            '\\' => {
+                current.push(c);
+                current.push(chars.next().ok_or_else(invalid)?);
+            }
+            '"' if depth > 0 => {
+                in_string = !in_string;
+                current.push(c);
+            }
+            _ if in_string => current.push(c),
+            '(' => {
+                depth += 1;
+                current.push(c);
+            }
+            ')' => {
+                depth = depth.checked_sub(1).ok_or_else(invalid)?;
+                current.push(c);
+            }
+            '.' if depth == 0 => components.push(String::new()),
+            '|' if depth == 0 => return Err(FilterError::Unsupported("gjson pipes".to_string())),
+            c => current.push(c),
+        }
+    }
+
+    if depth > 0 || in_string || components.iter().any(String::is_empty) {
+        return Err(invalid());
+    }
+    Ok(components)
+}
+
+/// Translates `#(key op value)` into the native selector for the first matching
+/// element, and `#(...)#` into the one for every matching element.
+fn parse_query(query: &str) -> Result<String, FilterError> {
+    let (condition, all) = match query.strip_suffix(")#") {
+        Some(condition) => (condition, true),
+        None => (
+            query
+                .strip_suffix(')')
+                .ok_or_else(|| FilterError::InvalidPath(query.to_string()))?,
+            false,
+        ),
+    };
+    let condition = &condition[2..];
+    let unsupported = || FilterError::Unsupported(format!("gjson query {}", query));
+
+    let at = condition
+        .find(['=', '!', '<', '>', '%'])
+        .ok_or_else(unsupported)?;
+    let (key, rest) = condition.split_at(at);
+    let (op, literal) = [
+        ("==", CompareOp::Eq),
+        ("!=", CompareOp::Ne),
+        ("<=", CompareOp::Le),
+        (">=", CompareOp::Ge),
+        ("<", CompareOp::Lt),
+        (">", CompareOp::Gt),
+    ]
+    .into_iter()
+    .find_map(|(symbol, op)| Some((op, rest.strip_prefix(symbol)?)))
+    .ok_or_else(unsupported)?;
+
+    let key = unescape(key.trim());
+    if key.is_empty() || key.contains('.') {
+        return Err(unsupported());
+    }
+    let value: Value = serde_json::from_str(literal.trim())
+        .map_err(|_| FilterError::InvalidPath(query.to_string()))?;
+    if value.to_string().contains(['[', ']']) {
+        return Err(unsupported());
+    }
+
+    Ok(PathSegment::Where {
+        key,
+        op,
+        value,
+        all,
+    }
+    .to_string())
+}
+
+fn has_unescaped(component: &str, special: &[char]) -> bool {
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if special.contains(&c) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn unescape(component: &str) -> String {
+    let mut key = String::with_capacity(component.len());
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => key.extend(chars.next()),
+            c => key.push(c),
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quantifier;
+    use serde_json::json;
+
+    fn sample() -> Value {
+        json!({
+            "name": { "first": "Tom", "last": "Anderson" },
+            "age": 37,
+            "children": ["Sara", "Alex", "Jack"],
+            "fav.movie": "Deer Hunter",
+            "friends": [
+                { "first": "Dale", "last": "Murphy", "age": 44, "nets": ["ig", "fb", "tw"] },
+                { "first": "Roger", "last": "Craig", "age": 68, "nets": ["fb", "tw"] },
+                { "first": "Jane", "last": "Murphy", "age": 47, "nets": ["ig", "tw"] }
+            ]
+        })
+    }
+
+    fn equals(path: &str, target: Value) -> Filter {
+        Filter::from_gjson(path, Operator::Equals(target)).unwrap()
+    }
+
+    #[test]
+    fn test_gjson_paths() {
+        let value = sample();
+
+        assert!(equals("name.last", json!("Anderson"))
+            .check(&value)
+            .unwrap());
+        assert!(equals("children.1", json!("Alex")).check(&value).unwrap());
+
+        // Numeric components are indices only, escaped ones are keys
+        let codes = json!({ "codes": { "200": "OK" } });
+        assert!(matches!(
+            equals("codes.200", json!("OK")).check(&codes),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+        assert!(equals(r"codes.\200", json!("OK")).check(&codes).unwrap());
+        assert!(equals(r"fav\.movie", json!("Deer Hunter"))
+            .check(&value)
+            .unwrap());
+
+        let filter = equals("children.#", json!(3));
+        assert_eq!(&*filter.path, "children#length");
+        assert!(filter.check(&value).unwrap());
+        assert!(equals("friends.#", json!(3)).check(&value).unwrap());
+        assert!(equals("friends.1.nets.#", json!(2)).check(&value).unwrap());
+
+        let filter = equals("friends.#.first", json!("Jane"));
+        assert_eq!(&*filter.path, "friends[].first");
+        assert!(filter.check(&value).unwrap());
+        assert!(!filter.check_quantified(&value, Quantifier::All).unwrap());
+    }
+
+    #[test]
+    fn test_gjson_queries() {
+        let value = sample();
+
+        let filter = equals(r#"friends.#(last=="Murphy").first"#, json!("Dale"));
+        assert_eq!(&*filter.path, "friends[last=Murphy].first");
+        assert!(filter.check(&value).unwrap());
+
+        assert!(equals("friends.#(age>45).first", json!("Roger"))
+            .check(&value)
+            .unwrap());
+        assert!(equals("friends.#(age <= 44).first", json!("Dale"))
+            .check(&value)
+            .unwrap());
+        assert!(equals(r#"friends.#(first!="Dale").last"#, json!("Craig"))
+            .check(&value)
+            .unwrap());
+
+        let filter = equals("friends.#(age>45)#.last", json!("Craig"));
+        assert_eq!(&*filter.path, r#"friends[?"age">45].last"#);
+        assert!(filter.check(&value).unwrap());
+        assert!(!filter.check_quantified(&value, Quantifier::All).unwrap());
+        assert_eq!(
+            filter.matching_paths(&value).unwrap(),
+            vec!["friends[1].last".to_string()]
+        );
+
+        assert!(matches!(
+            equals("friends.#(age>100).first", json!("Dale")).check(&value),
+            Err(FilterError::PathNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_gjson_unsupported() {
+        for path in [
+            r#"friends.#(first%"D*").last"#,
+            r#"friends.#(nets.#(=="fb"))#.first"#,
+            "children|@reverse",
+            "children.@reverse",
+            "child*.0",
+        ] {
+            assert!(
+                matches!(
+                    Filter::from_gjson(path, Operator::Exists(true)),
+                    Err(FilterError::Unsupported(..))
+                ),
+                "{}",
+                path
+            );
+        }
+
+        for path in ["", "a..b", "friends.#(age>45", r"a\"] {
+            assert!(
+                matches!(
+                    Filter::from_gjson(path, Operator::Exists(true)),
+                    Err(FilterError::InvalidPath(..))
+                ),
+                "{}",
+                path
+            );
+        }
+    }
+}
@@ -1,7 +1,38 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use thiserror::Error;
 
+mod compiled;
+mod describe;
+mod gjson;
+mod macros;
+mod mongo;
+mod partial;
+mod path;
+pub mod prelude;
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub use compiled::CompiledFilter;
+pub use partial::PartialFilter;
+
+#[doc(hidden)]
+pub use serde_json as __serde_json;
+
+use path::{parse_path, quote_key, ParsedPath, PathSegment};
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub enum Operator {
     // Numeric operators
@@ -9,167 +40,1232 @@ pub enum Operator {
     LessThan(f64),
     GreaterOrEqual(f64),
     LessOrEqual(f64),
+    /// Float equality within `max_ulps` units in the last place. Values of
+    /// opposite sign only match when both are zero.
+    UlpEquals {
+        target: f64,
+        max_ulps: u32,
+    },
+    /// `-0.0` counts as zero.
+    Sign(Sign),
+    /// Passes if the value divided by `scale` is greater than `value`, e.g.
+    /// `{ value: 10.0, scale: 100.0 }` compares an amount in cents against a
+    /// threshold of ten dollars. A zero `scale` is an error.
+    GreaterThanScaled {
+        value: f64,
+        scale: f64,
+    },
+    /// Passes if the value is an integer multiple (within a relative epsilon of
+    /// `1e-9`, so `0.3` is a multiple of `0.1`) of the number at another path,
+    /// resolved like `InPath`. A zero divisor is an `InvalidOperand` error.
+    MultipleOfPath(String),
+    /// Passes if the value lies between `min` and `max`, each bound inclusive
+    /// or exclusive. A `None` bound leaves that side unbounded.
+    Range {
+        min: Option<f64>,
+        max: Option<f64>,
+        min_inclusive: bool,
+        max_inclusive: bool,
+    },
+    /// Shorthand for a `Range` with both bounds set and equally inclusive. A
+    /// `min` greater than `max` never matches.
+    Between {
+        min: f64,
+        max: f64,
+        inclusive: bool,
+    },
+    /// Passes if the value lies in any of the inclusive `(min, max)` ranges.
+    /// Ranges with `min > max` are skipped.
+    InRanges(Vec<(f64, f64)>),
+    /// Passes if the value rounded to `decimals` places (half away from zero)
+    /// equals `value` rounded the same way, so `9.996` rounds to `10.0` at 2
+    /// decimals but not at 3.
+    RoundsTo {
+        value: f64,
+        decimals: u32,
+    },
+    /// Reduces the numbers at `field` (relative to each element, `.` for the
+    /// element itself) across the array at `array_path` and passes if the
+    /// result equals the number at `other_path`, within a relative epsilon of
+    /// `1e-9`. Both paths are relative to this path, so `{ array_path:
+    /// "lineItems", reducer: Sum, field: "amount", other_path: "total" }` checks
+    /// an invoice. `Min`, `Max` and `Average` of an empty array never match.
+    AggregateEqualsPath {
+        array_path: String,
+        reducer: Reducer,
+        field: String,
+        other_path: String,
+    },
+
+    // Byte size operators
+    #[cfg(feature = "byte-size")]
+    SizeGreaterThan(String),
+
+    // Geographic operators
+    /// Passes if the point whose latitude and longitude (in degrees) are at
+    /// `lat_path` and `lng_path`, relative to this path, lies within `km`
+    /// kilometers of (`lat`, `lng`) by great-circle (haversine) distance. Both
+    /// paths must select a single value, so that they belong to the same point;
+    /// to check several points, fan out in this filter's path instead, e.g.
+    /// `stores[]` with `lat_path: "lat"`.
+    #[cfg(feature = "geo")]
+    WithinDistance {
+        lat: f64,
+        lng: f64,
+        km: f64,
+        lat_path: String,
+        lng_path: String,
+    },
+
+    // Date-time operators
+    /// Passes if the RFC 3339 timestamp at this path is at most `duration_secs`
+    /// seconds before or after the one at `other_path`, resolved like `InPath`.
+    #[cfg(feature = "datetime")]
+    WithinDurationOf {
+        other_path: String,
+        duration_secs: i64,
+    },
 
     // General equality
     Equals(Value),
     NotEqual(Value),
+    /// Membership in a list of candidates, using the same equality as
+    /// `Equals` (so `1` matches `1.0`). An empty list never matches.
+    In(Vec<Value>),
+    /// Deep equality; with `ignore_array_order` arrays at every level compare as
+    /// multisets.
+    DeepEquals {
+        target: Value,
+        ignore_array_order: bool,
+    },
+    /// Membership (as in `In`) in the array found at another path, resolved
+    /// against the same value this filter's own path was resolved from.
+    /// Deserializes from `MemberOfPath` too, the spelling used by validation
+    /// rules such as `status` against `validStatuses`.
+    #[serde(alias = "MemberOfPath")]
+    InPath(String),
+    /// Equality (as in `Equals`) with the value at another path, resolved like
+    /// `InPath`. A missing path on either side is an error.
+    SameAs(String),
 
     // String operators
     StartsWith(String),
     EndsWith(String),
     Contains(String),
+    /// Case-insensitive counterparts of `Equals` on strings, `StartsWith`,
+    /// `EndsWith` and `Contains`; both sides are lowercased before comparing.
+    EqualsIgnoreCase(String),
+    StartsWithIgnoreCase(String),
+    EndsWithIgnoreCase(String),
+    ContainsIgnoreCase(String),
+    /// String equality after trimming Unicode whitespace from both sides of
+    /// both strings. Non-strings are a `TypeMismatch`.
+    EqualsTrimmed(String),
+    /// String equality after trimming and collapsing whitespace runs to a single space.
+    EqualsNormalizedWhitespace(String),
+    Format(FormatKind),
+    /// Passes if the string would convert to a finite number under
+    /// [`CoercionKind::Number`], without converting it.
+    LooksLikeNumber,
+    /// Like `LooksLikeNumber`, but only for whole numbers without a fraction
+    /// or exponent (e.g. `"42"`, not `"4.2"` or `"4e2"`) that fit in an `i64`.
+    LooksLikeInteger,
+    /// Passes if the string is all digits (at least two) and its last digit is a
+    /// valid Luhn check digit, as for credit card numbers. Strings with spaces or
+    /// other characters don't match.
+    Luhn,
+    /// Passes if the normalized Levenshtein similarity between the value and
+    /// `target` (1.0 for equal strings, 0.0 for entirely different ones) is at
+    /// least `min_similarity`. [`Filter::score`] reports the similarity itself.
+    FuzzyMatches {
+        target: String,
+        min_similarity: f64,
+    },
+    #[cfg(feature = "phonetic")]
+    Phonetic {
+        target: String,
+        algorithm: PhoneticAlgo,
+    },
+    /// Case-sensitive substring match ignoring diacritics on either side, so
+    /// `"Cafe"` is found in `"Le Café"` and `"Café"` in `"Le Cafe"`.
+    #[cfg(feature = "diacritics")]
+    ContainsNormalized(String),
+    /// Equality under the collation rules of `locale` (a BCP 47 tag such as
+    /// `de`, `sv` or `de-u-co-phonebk`), ignoring case and accents unless the
+    /// locale treats them as separate letters. Known languages without
+    /// locale-specific rules (e.g. `en`) use the root collation, while unknown
+    /// ones (e.g. `xx-YY`) are an `InvalidLocale` error.
+    #[cfg(feature = "collation")]
+    CollatedEquals {
+        target: String,
+        locale: String,
+    },
+    /// Passes if the hex digest of the string's UTF-8 bytes under `algorithm`
+    /// equals `expected`, ignoring case. `expected` must be a digest of the
+    /// right length.
+    #[cfg(feature = "hash")]
+    HashEquals {
+        algorithm: HashAlgo,
+        expected: String,
+    },
+    /// Passes if the string matches the regex `pattern` anywhere; anchor it
+    /// with `^...$` to match the whole string. The pattern is compiled on every
+    /// evaluation, so filters checked repeatedly should be compiled with
+    /// [`Filter::compile`], which compiles the pattern once.
+    #[cfg(feature = "regex")]
+    Regex(String),
+    /// Applies `inner` to capture group `group` (an index, `0` being the whole
+    /// match, or a name) of the first match of `pattern`. Evaluates to `false`
+    /// when the pattern or the group doesn't match.
+    #[cfg(feature = "regex")]
+    RegexCapture {
+        pattern: String,
+        group: CaptureGroup,
+        inner: Box<Operator>,
+    },
+    /// Matches against an already compiled regex, see [`Filter::regex_field`].
+    /// Filters holding one can't be serialized.
+    #[cfg(feature = "regex")]
+    #[serde(skip)]
+    MatchesRegex(CompiledRegex),
+    /// `RegexCapture` with an already compiled regex, produced by
+    /// [`Filter::compile`]. Filters holding one can't be serialized.
+    #[cfg(feature = "regex")]
+    #[serde(skip)]
+    MatchesRegexCapture {
+        regex: CompiledRegex,
+        group: CaptureGroup,
+        inner: Box<Operator>,
+    },
 
     // Array operators
     ArrayContains(Value),
+    /// Passes if the array contains (as in `ArrayContains`) every one of the
+    /// given values. An empty list always passes.
+    ArrayContainsAll(Vec<Value>),
+    /// Passes if the array contains (as in `ArrayContains`) at least one of
+    /// the given values. An empty list never passes.
+    ArrayContainsAny(Vec<Value>),
+    /// Passes if every element of the array equals (as in `Equals`) one of the
+    /// given values. An empty array always passes.
+    IsSubsetOf(Vec<Value>),
+    /// Compares the number of duplicates in the array, i.e. its length minus
+    /// the number of distinct elements (as in `Equals`), against `count`.
+    DuplicateCount {
+        op: CompareOp,
+        count: usize,
+    },
+    /// Element-wise equality (as in `Equals`) with `target`, skipping the
+    /// positions listed in `ignore`. Arrays of different lengths never match.
+    ArrayEqualsIgnoringIndices {
+        target: Vec<Value>,
+        ignore: Vec<usize>,
+    },
+    /// Applies `inner` to the length of the value: the number of characters
+    /// (Unicode scalar values, not bytes) of a string, elements of an array or
+    /// keys of an object, e.g. `Length(Box::new(Operator::GreaterThan(3.0)))`.
+    Length(Box<Operator>),
+    /// Flattens nested arrays up to `depth` levels (so `[[1, 2], [3, [4]]]` at
+    /// depth 1 becomes `[1, 2, 3, [4]]`), then applies `inner` to the result.
+    Flatten {
+        depth: usize,
+        inner: Box<Operator>,
+    },
+    /// Slides a window of `size` consecutive elements over the array and
+    /// applies `inner` to each window as a sub-array, so `[0]` is the first
+    /// element of the window. Passes if any / all windows match, depending on
+    /// `quantifier`. Never matches when there is no window, i.e. when `size`
+    /// is zero or larger than the array.
+    Window {
+        size: usize,
+        inner: Box<Filter>,
+        quantifier: Quantifier,
+    },
+    /// Evaluates the filter against every element of the array, with its path
+    /// relative to the element (so `.` is the element itself), and passes if
+    /// all of them match. An empty array always passes.
+    All(Box<Filter>),
+    /// Like `All`, but passes if at least one element matches. An empty array
+    /// never passes.
+    Any(Box<Filter>),
 
     // Object operators
     HasKey(String),
+    /// `Exists(true)` passes if the path resolves to any value (including
+    /// `null`), `Exists(false)` if it runs into a missing key.
+    Exists(bool),
+    /// Evaluates `inner` against the value of every entry whose key matches
+    /// `key_glob` (`*` matches any run of characters, `?` a single one), passing
+    /// if any or all of them match. When no key matches, `Any` yields `false`
+    /// and `All` yields `true`.
+    EntriesMatching {
+        key_glob: String,
+        inner: Box<Filter>,
+        quantifier: Quantifier,
+    },
+    /// Passes if every field of the object passes its own operator. A missing
+    /// field fails the match, or with `missing_is_error` the check, unless its
+    /// operator accepts missing paths (e.g. `OptionalMatch`).
+    Fields {
+        fields: BTreeMap<String, Operator>,
+        #[serde(default)]
+        missing_is_error: bool,
+    },
+
+    // Type operators
+    /// Passes if the value has the given JSON type. Never a type mismatch.
+    TypeIs(JsonType),
 
     // Logical operators
+    /// Passes if every filter matches. Filters are evaluated in order and the
+    /// rest are skipped after the first that doesn't match, so their errors
+    /// don't surface.
     And(Vec<Filter>),
+    /// Passes if any filter matches, skipping the rest (and their errors) after
+    /// the first that does.
     Or(Vec<Filter>),
+    /// Negates the inner filter, which is resolved against the same value.
+    /// Errors from the inner filter propagate rather than becoming `true`.
+    Not(Box<Filter>),
+
+    // Wrapping operators
+    /// Passes when the path is missing, otherwise applies the inner operator:
+    /// "if present, then valid".
+    OptionalMatch(Box<Operator>),
+    /// Applies the inner operator, treating a `TypeMismatch` from it as `false`
+    /// instead of an error. Other errors, including `PathNotFound`, still
+    /// propagate; wrap in `OptionalMatch` to also pass on missing paths.
+    OrFalseOnMismatch(Box<Operator>),
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
-pub struct Filter {
-    pub path: String,
-    pub operator: Operator,
+// `f64` and `Value` don't implement `Hash`, so operators are hashed field by
+// field, with floats hashed by their bits (`-0.0` as `0.0`, since the two
+// compare equal) and `Value`s by `hash_value`. Equal operators hash equal.
+impl Hash for Operator {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Operator::GreaterThan(target)
+            | Operator::LessThan(target)
+            | Operator::GreaterOrEqual(target)
+            | Operator::LessOrEqual(target) => hash_f64(*target, state),
+            Operator::UlpEquals { target, max_ulps } => {
+                hash_f64(*target, state);
+                max_ulps.hash(state);
+            }
+            Operator::Sign(sign) => sign.hash(state),
+            Operator::GreaterThanScaled { value, scale } => {
+                hash_f64(*value, state);
+                hash_f64(*scale, state);
+            }
+            Operator::Range {
+                min,
+                max,
+                min_inclusive,
+                max_inclusive,
+            } => {
+                for bound in [min, max] {
+                    bound.is_some().hash(state);
+                    bound.iter().for_each(|bound| hash_f64(*bound, state));
+                }
+                min_inclusive.hash(state);
+                max_inclusive.hash(state);
+            }
+            Operator::Between {
+                min,
+                max,
+                inclusive,
+            } => {
+                hash_f64(*min, state);
+                hash_f64(*max, state);
+                inclusive.hash(state);
+            }
+            Operator::InRanges(ranges) => {
+                ranges.len().hash(state);
+                for (min, max) in ranges {
+                    hash_f64(*min, state);
+                    hash_f64(*max, state);
+                }
+            }
+            Operator::RoundsTo { value, decimals } => {
+                hash_f64(*value, state);
+                decimals.hash(state);
+            }
+            Operator::AggregateEqualsPath {
+                array_path,
+                reducer,
+                field,
+                other_path,
+            } => {
+                array_path.hash(state);
+                reducer.hash(state);
+                field.hash(state);
+                other_path.hash(state);
+            }
+            #[cfg(feature = "geo")]
+            Operator::WithinDistance {
+                lat,
+                lng,
+                km,
+                lat_path,
+                lng_path,
+            } => {
+                hash_f64(*lat, state);
+                hash_f64(*lng, state);
+                hash_f64(*km, state);
+                lat_path.hash(state);
+                lng_path.hash(state);
+            }
+            #[cfg(feature = "datetime")]
+            Operator::WithinDurationOf {
+                other_path,
+                duration_secs,
+            } => {
+                other_path.hash(state);
+                duration_secs.hash(state);
+            }
+            Operator::Equals(target)
+            | Operator::NotEqual(target)
+            | Operator::ArrayContains(target) => hash_value(target, state),
+            Operator::In(values)
+            | Operator::ArrayContainsAll(values)
+            | Operator::ArrayContainsAny(values)
+            | Operator::IsSubsetOf(values) => hash_values(values, state),
+            Operator::DeepEquals {
+                target,
+                ignore_array_order,
+            } => {
+                hash_value(target, state);
+                ignore_array_order.hash(state);
+            }
+            #[cfg(feature = "byte-size")]
+            Operator::SizeGreaterThan(operand) => operand.hash(state),
+            Operator::MultipleOfPath(operand)
+            | Operator::InPath(operand)
+            | Operator::SameAs(operand)
+            | Operator::StartsWith(operand)
+            | Operator::EndsWith(operand)
+            | Operator::Contains(operand)
+            | Operator::EqualsIgnoreCase(operand)
+            | Operator::StartsWithIgnoreCase(operand)
+            | Operator::EndsWithIgnoreCase(operand)
+            | Operator::ContainsIgnoreCase(operand)
+            | Operator::EqualsTrimmed(operand)
+            | Operator::EqualsNormalizedWhitespace(operand)
+            | Operator::HasKey(operand) => operand.hash(state),
+            Operator::Format(kind) => kind.hash(state),
+            Operator::LooksLikeNumber | Operator::LooksLikeInteger | Operator::Luhn => {}
+            Operator::FuzzyMatches {
+                target,
+                min_similarity,
+            } => {
+                target.hash(state);
+                hash_f64(*min_similarity, state);
+            }
+            #[cfg(feature = "phonetic")]
+            Operator::Phonetic { target, algorithm } => {
+                target.hash(state);
+                algorithm.hash(state);
+            }
+            #[cfg(feature = "diacritics")]
+            Operator::ContainsNormalized(target) => target.hash(state),
+            #[cfg(feature = "collation")]
+            Operator::CollatedEquals { target, locale } => {
+                target.hash(state);
+                locale.hash(state);
+            }
+            #[cfg(feature = "hash")]
+            Operator::HashEquals {
+                algorithm,
+                expected,
+            } => {
+                algorithm.hash(state);
+                expected.hash(state);
+            }
+            #[cfg(feature = "regex")]
+            Operator::Regex(pattern) => pattern.hash(state),
+            #[cfg(feature = "regex")]
+            Operator::RegexCapture {
+                pattern,
+                group,
+                inner,
+            } => {
+                pattern.hash(state);
+                group.hash(state);
+                inner.hash(state);
+            }
+            #[cfg(feature = "regex")]
+            Operator::MatchesRegex(regex) => regex.0.as_str().hash(state),
+            #[cfg(feature = "regex")]
+            Operator::MatchesRegexCapture {
+                regex,
+                group,
+                inner,
+            } => {
+                regex.0.as_str().hash(state);
+                group.hash(state);
+                inner.hash(state);
+            }
+            Operator::DuplicateCount { op, count } => {
+                op.hash(state);
+                count.hash(state);
+            }
+            Operator::ArrayEqualsIgnoringIndices { target, ignore } => {
+                hash_values(target, state);
+                ignore.hash(state);
+            }
+            Operator::Length(inner)
+            | Operator::OptionalMatch(inner)
+            | Operator::OrFalseOnMismatch(inner) => inner.hash(state),
+            Operator::Flatten { depth, inner } => {
+                depth.hash(state);
+                inner.hash(state);
+            }
+            Operator::Window {
+                size,
+                inner,
+                quantifier,
+            } => {
+                size.hash(state);
+                inner.hash(state);
+                quantifier.hash(state);
+            }
+            Operator::All(inner) | Operator::Any(inner) | Operator::Not(inner) => inner.hash(state),
+            Operator::Exists(exists) => exists.hash(state),
+            Operator::EntriesMatching {
+                key_glob,
+                inner,
+                quantifier,
+            } => {
+                key_glob.hash(state);
+                inner.hash(state);
+                quantifier.hash(state);
+            }
+            Operator::Fields {
+                fields,
+                missing_is_error,
+            } => {
+                fields.hash(state);
+                missing_is_error.hash(state);
+            }
+            Operator::TypeIs(kind) => kind.hash(state),
+            Operator::And(filters) | Operator::Or(filters) => filters.hash(state),
+        }
+    }
 }
 
-#[derive(Error, Debug)]
-pub enum FilterError {
-    #[error("Path not found: {0}")]
-    PathNotFound(String),
+fn hash_f64<H: Hasher>(number: f64, state: &mut H) {
+    let number = if number == 0.0 { 0.0 } else { number };
+    number.to_bits().hash(state);
+}
 
-    #[error("Type mismatch: expected {expected}, got {got}")]
-    TypeMismatch { expected: String, got: String },
+fn hash_values<H: Hasher>(values: &[Value], state: &mut H) {
+    values.len().hash(state);
+    values.iter().for_each(|value| hash_value(value, state));
+}
 
-    #[error("Invalid array index in path: {0}")]
-    InvalidArrayIndex(String),
+/// Hashes a `Value` consistently with its `PartialEq`, under which numbers
+/// only equal numbers of the same representation.
+fn hash_value<H: Hasher>(value: &Value, state: &mut H) {
+    std::mem::discriminant(value).hash(state);
+    match value {
+        Value::Null => {}
+        Value::Bool(b) => b.hash(state),
+        Value::Number(n) => match (n.as_u64(), n.as_i64(), n.as_f64()) {
+            (Some(u), _, _) => (0u8, u).hash(state),
+            (None, Some(i), _) => (1u8, i).hash(state),
+            (None, None, float) => {
+                2u8.hash(state);
+                hash_f64(float.unwrap_or(f64::NAN), state);
+            }
+        },
+        Value::String(s) => s.hash(state),
+        Value::Array(items) => hash_values(items, state),
+        Value::Object(map) => {
+            map.len().hash(state);
+            for (key, value) in map {
+                key.hash(state);
+                hash_value(value, state);
+            }
+        }
+    }
+}
 
-    #[error("Invalid path format: {0}")]
-    InvalidPath(String),
+/// A path read by an operator, see [`Operator::operand_paths`].
+enum OperandPath {
+    /// Resolved against the same value as the filter's own path, like `InPath`.
+    Scoped(String),
+    /// Relative to the value the operator receives.
+    Relative(String),
 }
 
-impl Filter {
-    pub fn new(path: impl Into<String>, operator: Operator) -> Self {
-        Self {
-            path: path.into(),
-            operator,
+/// A condition on the value at `path`.
+///
+/// Filters are `Send + Sync`, so a single filter can be shared between threads.
+/// Documents shared as `Arc<Value>` can be passed as `&doc` directly, since
+/// `&Arc<Value>` derefs to `&Value`.
+#[derive(Debug, Clone, PartialEq, Hash, Deserialize, Serialize)]
+pub struct Filter {
+    pub path: Arc<str>,
+    pub operator: Operator,
+}
+
+impl Operator {
+    /// Nested filters evaluated against the value this operator receives.
+    ///
+    /// `Window` is left out: its filter sees sub-arrays that don't exist in the
+    /// document, so for paths and projections it counts as a leaf reading the
+    /// whole array.
+    fn subfilters(&self) -> &[Filter] {
+        match self {
+            Operator::And(filters) | Operator::Or(filters) => filters,
+            Operator::Not(inner)
+            | Operator::All(inner)
+            | Operator::Any(inner)
+            | Operator::EntriesMatching { inner, .. } => std::slice::from_ref(inner),
+            Operator::Flatten { inner, .. }
+            | Operator::Length(inner)
+            | Operator::OptionalMatch(inner)
+            | Operator::OrFalseOnMismatch(inner) => inner.subfilters(),
+            _ => &[],
         }
     }
 
-    pub fn check(&self, value: &Value) -> Result<bool, FilterError> {
-        let target = self.resolve_path(value)?;
-        self.check_operator(target)
+    /// Paths this operator reads besides the value it receives, such as the
+    /// array of `InPath` or the coordinates of `WithinDistance`.
+    fn operand_paths(&self) -> Vec<OperandPath> {
+        match self {
+            Operator::InPath(path) | Operator::SameAs(path) | Operator::MultipleOfPath(path) => {
+                vec![OperandPath::Scoped(path.clone())]
+            }
+            #[cfg(feature = "datetime")]
+            Operator::WithinDurationOf { other_path, .. } => {
+                vec![OperandPath::Scoped(other_path.clone())]
+            }
+            Operator::AggregateEqualsPath {
+                array_path,
+                field,
+                other_path,
+                ..
+            } => {
+                let elements = nested_path(array_path, "[]");
+                vec![
+                    OperandPath::Relative(array_path.clone()),
+                    OperandPath::Relative(nested_path(&elements, field)),
+                    OperandPath::Relative(other_path.clone()),
+                ]
+            }
+            #[cfg(feature = "geo")]
+            Operator::WithinDistance {
+                lat_path, lng_path, ..
+            } => vec![
+                OperandPath::Relative(lat_path.clone()),
+                OperandPath::Relative(lng_path.clone()),
+            ],
+            Operator::Fields { fields, .. } => fields
+                .iter()
+                .flat_map(|(key, operator)| {
+                    let key = quote_key(key).into_owned();
+                    let nested = operator.operand_paths().into_iter().map(|path| match path {
+                        OperandPath::Relative(path) => {
+                            OperandPath::Relative(nested_path(&key, &path))
+                        }
+                        scoped => scoped,
+                    });
+                    std::iter::once(OperandPath::Relative(key.clone()))
+                        .chain(nested)
+                        .collect::<Vec<_>>()
+                })
+                .collect(),
+            Operator::OptionalMatch(inner) | Operator::OrFalseOnMismatch(inner) => {
+                inner.operand_paths()
+            }
+            // The inner operator receives a computed value, so only its paths
+            // resolved against the scope exist in the document
+            Operator::Length(inner) | Operator::Flatten { inner, .. } => inner
+                .operand_paths()
+                .into_iter()
+                .filter(|path| matches!(path, OperandPath::Scoped(_)))
+                .collect(),
+            #[cfg(feature = "regex")]
+            Operator::RegexCapture { inner, .. } | Operator::MatchesRegexCapture { inner, .. } => {
+                inner
+                    .operand_paths()
+                    .into_iter()
+                    .filter(|path| matches!(path, OperandPath::Scoped(_)))
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
     }
 
-    fn resolve_path<'a>(&self, value: &'a Value) -> Result<&'a Value, FilterError> {
-        let mut current = value;
+    /// The result of operators that are defined for paths that don't resolve
+    /// (`PathNotFound`), rather than propagating the error.
+    fn missing_result(&self) -> Option<bool> {
+        match self {
+            Operator::OptionalMatch(_) => Some(true),
+            Operator::Exists(exists) => Some(!exists),
+            Operator::OrFalseOnMismatch(inner) => inner.missing_result(),
+            _ => None,
+        }
+    }
 
-        if self.path == "." {
-            return Ok(current);
+    /// The empty array or object that container operators see in place of a
+    /// missing path under [`FilterOptions::missing_containers_empty`].
+    fn empty_container(&self) -> Option<Value> {
+        match self {
+            Operator::ArrayContains(_)
+            | Operator::ArrayContainsAll(_)
+            | Operator::ArrayContainsAny(_)
+            | Operator::IsSubsetOf(_)
+            | Operator::DuplicateCount { .. }
+            | Operator::ArrayEqualsIgnoringIndices { .. }
+            | Operator::Flatten { .. }
+            | Operator::Window { .. }
+            | Operator::All(_)
+            | Operator::Any(_)
+            | Operator::Length(_) => Some(Value::Array(Vec::new())),
+            Operator::HasKey(_) | Operator::Fields { .. } | Operator::EntriesMatching { .. } => {
+                Some(Value::Object(Map::new()))
+            }
+            Operator::OrFalseOnMismatch(inner) => inner.empty_container(),
+            _ => None,
         }
+    }
 
-        for segment in self.path.split('.') {
-            if segment.contains('[') && segment.ends_with(']') {
-                let (field, index) = self.parse_array_segment(segment)?;
+    /// Validates the operands of this operator, but not those of nested
+    /// filters, see [`Filter::validate`].
+    fn validate(&self) -> Result<(), FilterError> {
+        let not_nan = |n: f64| {
+            if n.is_nan() {
+                Err(FilterError::InvalidOperand(format!("NaN in {:?}", self)))
+            } else {
+                Ok(())
+            }
+        };
 
-                if !field.is_empty() {
-                    current = current
-                        .get(&field)
-                        .ok_or_else(|| FilterError::PathNotFound(field.to_string()))?;
+        match self {
+            Operator::GreaterThan(n)
+            | Operator::LessThan(n)
+            | Operator::GreaterOrEqual(n)
+            | Operator::LessOrEqual(n)
+            | Operator::UlpEquals { target: n, .. }
+            | Operator::FuzzyMatches {
+                min_similarity: n, ..
+            } => not_nan(*n),
+            Operator::GreaterThanScaled { value, scale } => {
+                not_nan(*value)?;
+                not_nan(*scale)?;
+                if *scale == 0.0 {
+                    return Err(FilterError::InvalidOperand(format!(
+                        "zero scale in {:?}",
+                        self
+                    )));
                 }
-
-                current = match current {
-                    Value::Array(arr) => arr
-                        .get(index)
-                        .ok_or_else(|| FilterError::InvalidArrayIndex(index.to_string()))?,
-                    _ => {
-                        return Err(FilterError::TypeMismatch {
-                            expected: "array".to_string(),
-                            got: format!("{:?}", current),
-                        })
-                    }
-                };
-            } else {
-                current = current
-                    .get(segment)
-                    .ok_or_else(|| FilterError::PathNotFound(segment.to_string()))?;
+                Ok(())
+            }
+            Operator::Range { min, max, .. } => {
+                min.map_or(Ok(()), not_nan)?;
+                max.map_or(Ok(()), not_nan)
+            }
+            Operator::Between { min, max, .. } => {
+                not_nan(*min)?;
+                not_nan(*max)
+            }
+            Operator::RoundsTo { value, .. } => not_nan(*value),
+            Operator::AggregateEqualsPath {
+                array_path,
+                field,
+                other_path,
+                ..
+            } => {
+                parse_path(array_path)?;
+                parse_path(field)?;
+                parse_path(other_path).map(drop)
+            }
+            #[cfg(feature = "geo")]
+            Operator::WithinDistance {
+                lat,
+                lng,
+                km,
+                lat_path,
+                lng_path,
+            } => {
+                not_nan(*lat)?;
+                not_nan(*lng)?;
+                not_nan(*km)?;
+                coordinate_path(lat_path)?;
+                coordinate_path(lng_path).map(drop)
+            }
+            Operator::InRanges(ranges) => ranges.iter().try_for_each(|&(min, max)| {
+                not_nan(min)?;
+                not_nan(max)
+            }),
+            Operator::InPath(path) | Operator::SameAs(path) | Operator::MultipleOfPath(path) => {
+                parse_path(path).map(drop)
+            }
+            #[cfg(feature = "datetime")]
+            Operator::WithinDurationOf { other_path, .. } => parse_path(other_path).map(drop),
+            #[cfg(feature = "byte-size")]
+            Operator::SizeGreaterThan(s) => match parse_byte_size(s) {
+                Some(_) => Ok(()),
+                None => Err(FilterError::InvalidByteSize(s.to_string())),
+            },
+            #[cfg(feature = "regex")]
+            Operator::Regex(pattern) => regex::Regex::new(pattern)
+                .map(drop)
+                .map_err(|e| FilterError::InvalidRegex(e.to_string())),
+            #[cfg(feature = "regex")]
+            Operator::RegexCapture {
+                pattern,
+                group,
+                inner,
+            } => {
+                let regex = regex::Regex::new(pattern)
+                    .map_err(|e| FilterError::InvalidRegex(e.to_string()))?;
+                group.validate(&regex)?;
+                inner.validate()
+            }
+            #[cfg(feature = "regex")]
+            Operator::MatchesRegexCapture {
+                regex,
+                group,
+                inner,
+            } => {
+                group.validate(&regex.0)?;
+                inner.validate()
+            }
+            #[cfg(feature = "collation")]
+            Operator::CollatedEquals { locale, .. } => collator(locale).map(drop),
+            #[cfg(feature = "hash")]
+            Operator::HashEquals {
+                algorithm,
+                expected,
+            } => {
+                if expected.len() != algorithm.hex_len()
+                    || !expected.bytes().all(|b| b.is_ascii_hexdigit())
+                {
+                    return Err(FilterError::InvalidOperand(format!(
+                        "{} is not a {:?} digest",
+                        expected, algorithm
+                    )));
+                }
+                Ok(())
             }
+            Operator::Flatten { inner, .. }
+            | Operator::Length(inner)
+            | Operator::OptionalMatch(inner)
+            | Operator::OrFalseOnMismatch(inner) => inner.validate(),
+            Operator::Window { inner, .. } => inner.validate(),
+            Operator::Fields { fields, .. } => fields.values().try_for_each(|operator| {
+                operator.validate()?;
+                operator.subfilters().iter().try_for_each(Filter::validate)
+            }),
+            _ => Ok(()),
         }
+    }
 
-        Ok(current)
+    /// The variant name, e.g. `GreaterThan`.
+    fn name(&self) -> &'static str {
+        match self {
+            Operator::GreaterThan(_) => "GreaterThan",
+            Operator::LessThan(_) => "LessThan",
+            Operator::GreaterOrEqual(_) => "GreaterOrEqual",
+            Operator::LessOrEqual(_) => "LessOrEqual",
+            Operator::UlpEquals { .. } => "UlpEquals",
+            Operator::Sign(_) => "Sign",
+            Operator::GreaterThanScaled { .. } => "GreaterThanScaled",
+            Operator::MultipleOfPath(_) => "MultipleOfPath",
+            Operator::Range { .. } => "Range",
+            Operator::Between { .. } => "Between",
+            Operator::InRanges(_) => "InRanges",
+            Operator::RoundsTo { .. } => "RoundsTo",
+            Operator::AggregateEqualsPath { .. } => "AggregateEqualsPath",
+            #[cfg(feature = "byte-size")]
+            Operator::SizeGreaterThan(_) => "SizeGreaterThan",
+            #[cfg(feature = "geo")]
+            Operator::WithinDistance { .. } => "WithinDistance",
+            #[cfg(feature = "datetime")]
+            Operator::WithinDurationOf { .. } => "WithinDurationOf",
+            Operator::Equals(_) => "Equals",
+            Operator::NotEqual(_) => "NotEqual",
+            Operator::In(_) => "In",
+            Operator::DeepEquals { .. } => "DeepEquals",
+            Operator::InPath(_) => "InPath",
+            Operator::SameAs(_) => "SameAs",
+            Operator::StartsWith(_) => "StartsWith",
+            Operator::EndsWith(_) => "EndsWith",
+            Operator::Contains(_) => "Contains",
+            Operator::EqualsIgnoreCase(_) => "EqualsIgnoreCase",
+            Operator::StartsWithIgnoreCase(_) => "StartsWithIgnoreCase",
+            Operator::EndsWithIgnoreCase(_) => "EndsWithIgnoreCase",
+            Operator::ContainsIgnoreCase(_) => "ContainsIgnoreCase",
+            Operator::EqualsTrimmed(_) => "EqualsTrimmed",
+            Operator::EqualsNormalizedWhitespace(_) => "EqualsNormalizedWhitespace",
+            Operator::Format(_) => "Format",
+            Operator::LooksLikeNumber => "LooksLikeNumber",
+            Operator::LooksLikeInteger => "LooksLikeInteger",
+            Operator::Luhn => "Luhn",
+            Operator::FuzzyMatches { .. } => "FuzzyMatches",
+            #[cfg(feature = "phonetic")]
+            Operator::Phonetic { .. } => "Phonetic",
+            #[cfg(feature = "diacritics")]
+            Operator::ContainsNormalized(_) => "ContainsNormalized",
+            #[cfg(feature = "collation")]
+            Operator::CollatedEquals { .. } => "CollatedEquals",
+            #[cfg(feature = "hash")]
+            Operator::HashEquals { .. } => "HashEquals",
+            #[cfg(feature = "regex")]
+            Operator::Regex(_) => "Regex",
+            #[cfg(feature = "regex")]
+            Operator::RegexCapture { .. } => "RegexCapture",
+            #[cfg(feature = "regex")]
+            Operator::MatchesRegex(_) => "MatchesRegex",
+            #[cfg(feature = "regex")]
+            Operator::MatchesRegexCapture { .. } => "MatchesRegexCapture",
+            Operator::ArrayContains(_) => "ArrayContains",
+            Operator::ArrayContainsAll(_) => "ArrayContainsAll",
+            Operator::ArrayContainsAny(_) => "ArrayContainsAny",
+            Operator::IsSubsetOf(_) => "IsSubsetOf",
+            Operator::DuplicateCount { .. } => "DuplicateCount",
+            Operator::ArrayEqualsIgnoringIndices { .. } => "ArrayEqualsIgnoringIndices",
+            Operator::Length(_) => "Length",
+            Operator::Flatten { .. } => "Flatten",
+            Operator::Window { .. } => "Window",
+            Operator::All(_) => "All",
+            Operator::Any(_) => "Any",
+            Operator::HasKey(_) => "HasKey",
+            Operator::Exists(_) => "Exists",
+            Operator::EntriesMatching { .. } => "EntriesMatching",
+            Operator::Fields { .. } => "Fields",
+            Operator::TypeIs(_) => "TypeIs",
+            Operator::And(_) => "And",
+            Operator::Or(_) => "Or",
+            Operator::Not(_) => "Not",
+            Operator::OptionalMatch(_) => "OptionalMatch",
+            Operator::OrFalseOnMismatch(_) => "OrFalseOnMismatch",
+        }
     }
 
-    fn parse_array_segment(&self, segment: &str) -> Result<(String, usize), FilterError> {
-        let bracket_idx = segment
-            .find('[')
-            .ok_or_else(|| FilterError::InvalidPath(segment.to_string()))?;
+    /// Graded counterpart of [`Operator::evaluate`], see [`Filter::score`].
+    fn score(&self, value: &Value, scope: &Value) -> Result<f64, FilterError> {
+        match self {
+            Operator::And(filters) => filters
+                .iter()
+                .try_fold(1.0, |min: f64, filter| Ok(min.min(filter.score(value)?))),
+
+            Operator::Or(filters) => filters
+                .iter()
+                .try_fold(0.0, |max: f64, filter| Ok(max.max(filter.score(value)?))),
+
+            Operator::Not(filter) => Ok(1.0 - filter.score(value)?),
+
+            Operator::FuzzyMatches { target, .. } => {
+                if let Value::String(str) = value {
+                    Ok(similarity(str, target))
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
 
-        let field = segment[..bracket_idx].to_string();
-        let index_str = &segment[bracket_idx + 1..segment.len() - 1];
+            Operator::OptionalMatch(inner) => inner.score(value, scope),
 
-        let index = index_str
-            .parse::<usize>()
-            .map_err(|_| FilterError::InvalidArrayIndex(index_str.to_string()))?;
+            Operator::OrFalseOnMismatch(inner) => match inner.score(value, scope) {
+                Err(FilterError::TypeMismatch { .. }) => Ok(0.0),
+                result => result,
+            },
 
-        Ok((field, index))
+            operator => Ok(if operator.evaluate(value, scope, &Context::default())? {
+                1.0
+            } else {
+                0.0
+            }),
+        }
     }
 
-    fn check_operator(&self, value: &Value) -> Result<bool, FilterError> {
-        match &self.operator {
+    fn evaluate(&self, value: &Value, scope: &Value, ctx: &Context) -> Result<bool, FilterError> {
+        match self {
             Operator::GreaterThan(n) => {
-                if let Value::Number(num) = value {
-                    Ok(num.as_f64().unwrap() > *n)
+                Ok(number(value)?.partial_cmp_f64(*n) == Some(Ordering::Greater))
+            }
+
+            Operator::LessThan(n) => Ok(number(value)?.partial_cmp_f64(*n) == Some(Ordering::Less)),
+
+            Operator::GreaterOrEqual(n) => Ok(matches!(
+                number(value)?.partial_cmp_f64(*n),
+                Some(Ordering::Greater | Ordering::Equal)
+            )),
+
+            Operator::LessOrEqual(n) => Ok(matches!(
+                number(value)?.partial_cmp_f64(*n),
+                Some(Ordering::Less | Ordering::Equal)
+            )),
+
+            #[cfg(feature = "byte-size")]
+            Operator::SizeGreaterThan(s) => {
+                if let Value::String(str) = value {
+                    let size = parse_byte_size(str)
+                        .ok_or_else(|| FilterError::InvalidByteSize(str.to_string()))?;
+                    let threshold = parse_byte_size(s)
+                        .ok_or_else(|| FilterError::InvalidByteSize(s.to_string()))?;
+                    Ok(size > threshold)
                 } else {
                     Err(FilterError::TypeMismatch {
-                        expected: "number".to_string(),
+                        expected: "string".to_string(),
                         got: format!("{:?}", value),
                     })
                 }
             }
 
-            Operator::LessThan(n) => {
-                if let Value::Number(num) = value {
-                    Ok(num.as_f64().unwrap() < *n)
+            #[cfg(feature = "datetime")]
+            Operator::WithinDurationOf {
+                other_path,
+                duration_secs,
+            } => {
+                let timestamp = parse_timestamp(value)?;
+                let max_millis = i128::from(*duration_secs) * 1000;
+                for (_, other) in resolve_branches(other_path, scope)? {
+                    let difference = (timestamp - parse_timestamp(&other)?).num_milliseconds();
+                    if i128::from(difference).abs() <= max_millis {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+
+            #[cfg(feature = "geo")]
+            Operator::WithinDistance {
+                lat,
+                lng,
+                km,
+                lat_path,
+                lng_path,
+            } => {
+                let coordinate = |path: &str| -> Result<f64, FilterError> {
+                    let branches = resolve_parsed(&coordinate_path(path)?, value)?;
+                    let (_, coordinate) = branches
+                        .first()
+                        .ok_or_else(|| FilterError::PathNotFound(path.to_string()))?;
+                    Ok(number(coordinate)?.to_f64())
+                };
+                let (point_lat, point_lng) = (coordinate(lat_path)?, coordinate(lng_path)?);
+                Ok(haversine_km(point_lat, point_lng, *lat, *lng) <= *km)
+            }
+
+            Operator::UlpEquals { target, max_ulps } => {
+                Ok(ulps_equal(number(value)?.to_f64(), *target, *max_ulps))
+            }
+
+            Operator::GreaterThanScaled {
+                value: threshold,
+                scale,
+            } => {
+                if *scale == 0.0 {
+                    return Err(FilterError::InvalidOperand(format!(
+                        "zero scale in {:?}",
+                        self
+                    )));
+                }
+                Ok(number(value)?.to_f64() / scale > *threshold)
+            }
+
+            Operator::RoundsTo {
+                value: target,
+                decimals,
+            } => {
+                let number = number(value)?.to_f64();
+                let scale = 10f64.powi(i32::try_from(*decimals).unwrap_or(i32::MAX));
+                let (scaled, target_scaled) = (number * scale, target * scale);
+                if !scaled.is_finite() || !target_scaled.is_finite() {
+                    // Too many decimals for rounding to change anything
+                    return Ok(number == *target);
+                }
+                Ok(scaled.round() == target_scaled.round())
+            }
+
+            Operator::AggregateEqualsPath {
+                array_path,
+                reducer,
+                field,
+                other_path,
+            } => {
+                let mut numbers = Vec::new();
+                for (_, array) in resolve_branches(array_path, value)? {
+                    for element in as_array(&array)? {
+                        for (_, n) in resolve_branches(field, element)? {
+                            numbers.push(number(&n)?.to_f64());
+                        }
+                    }
+                }
+                let Some(aggregate) = reducer.reduce(&numbers) else {
+                    return Ok(false);
+                };
+
+                for (_, other) in resolve_branches(other_path, value)? {
+                    let other = number(&other)?.to_f64();
+                    if (aggregate - other).abs() <= 1e-9 * aggregate.abs().max(other.abs()).max(1.0)
+                    {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+
+            Operator::MultipleOfPath(path) => {
+                let dividend = number(value)?.to_f64();
+                for (_, divisor) in resolve_branches(path, scope)? {
+                    let divisor = number(&divisor)?.to_f64();
+                    if divisor == 0.0 {
+                        return Err(FilterError::InvalidOperand(format!(
+                            "zero divisor at {}",
+                            path
+                        )));
+                    }
+                    let ratio = dividend / divisor;
+                    if (ratio - ratio.round()).abs() <= 1e-9 * ratio.abs().max(1.0) {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+
+            Operator::Range {
+                min,
+                max,
+                min_inclusive,
+                max_inclusive,
+            } => {
+                let number = number(value)?;
+                let above_min = min.is_none_or(|min| match number.partial_cmp_f64(min) {
+                    Some(Ordering::Greater) => true,
+                    Some(Ordering::Equal) => *min_inclusive,
+                    _ => false,
+                });
+                let below_max = max.is_none_or(|max| match number.partial_cmp_f64(max) {
+                    Some(Ordering::Less) => true,
+                    Some(Ordering::Equal) => *max_inclusive,
+                    _ => false,
+                });
+                Ok(above_min && below_max)
+            }
+
+            Operator::Between {
+                min,
+                max,
+                inclusive,
+            } => Operator::Range {
+                min: Some(*min),
+                max: Some(*max),
+                min_inclusive: *inclusive,
+                max_inclusive: *inclusive,
+            }
+            .evaluate(value, scope, ctx),
+
+            Operator::InRanges(ranges) => {
+                let number = number(value)?;
+                Ok(ranges.iter().any(|&(min, max)| {
+                    number.partial_cmp_f64(min).is_some_and(Ordering::is_ge)
+                        && number.partial_cmp_f64(max).is_some_and(Ordering::is_le)
+                }))
+            }
+
+            Operator::Sign(sign) => {
+                let ordering = number(value)?.partial_cmp_f64(0.0);
+                Ok(match sign {
+                    Sign::Positive => ordering == Some(Ordering::Greater),
+                    Sign::Negative => ordering == Some(Ordering::Less),
+                    Sign::Zero => ordering == Some(Ordering::Equal),
+                    Sign::NonNegative => ordering.is_some_and(Ordering::is_ge),
+                    Sign::NonPositive => ordering.is_some_and(Ordering::is_le),
+                })
+            }
+
+            Operator::Equals(target) => Ok(ctx.values_equal(value, target)),
+
+            Operator::NotEqual(target) => Ok(!ctx.values_equal(value, target)),
+
+            Operator::In(candidates) => Ok(candidates
+                .iter()
+                .any(|candidate| ctx.values_equal(value, candidate))),
+
+            Operator::DeepEquals {
+                target,
+                ignore_array_order,
+            } => Ok(deep_equals(value, target, *ignore_array_order)),
+
+            Operator::InPath(path) => {
+                for (_, candidates) in resolve_branches(path, scope)? {
+                    if let Value::Array(arr) = &*candidates {
+                        if arr.iter().any(|candidate| values_equal(value, candidate)) {
+                            return Ok(true);
+                        }
+                    } else {
+                        return Err(FilterError::TypeMismatch {
+                            expected: "array".to_string(),
+                            got: format!("{:?}", candidates),
+                        });
+                    }
+                }
+                Ok(false)
+            }
+
+            Operator::SameAs(path) => {
+                for (_, other) in resolve_branches(path, scope)? {
+                    if values_equal(value, &other) {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+
+            Operator::StartsWith(s) => {
+                if let Value::String(str) = value {
+                    Ok(str.starts_with(s))
                 } else {
                     Err(FilterError::TypeMismatch {
-                        expected: "number".to_string(),
+                        expected: "string".to_string(),
                         got: format!("{:?}", value),
                     })
                 }
             }
 
-            Operator::GreaterOrEqual(n) => {
-                if let Value::Number(num) = value {
-                    Ok(num.as_f64().unwrap() >= *n)
+            Operator::EndsWith(s) => {
+                if let Value::String(str) = value {
+                    Ok(str.ends_with(s))
                 } else {
                     Err(FilterError::TypeMismatch {
-                        expected: "number".to_string(),
+                        expected: "string".to_string(),
                         got: format!("{:?}", value),
                     })
                 }
             }
 
-            Operator::LessOrEqual(n) => {
-                if let Value::Number(num) = value {
-                    Ok(num.as_f64().unwrap() <= *n)
+            Operator::Contains(s) => {
+                if let Value::String(str) = value {
+                    Ok(str.contains(s))
                 } else {
                     Err(FilterError::TypeMismatch {
-                        expected: "number".to_string(),
+                        expected: "string".to_string(),
                         got: format!("{:?}", value),
                     })
                 }
             }
 
-            Operator::Equals(target) => Ok(value == target),
-
-            Operator::NotEqual(target) => Ok(value != target),
-
-            Operator::StartsWith(s) => {
+            Operator::EqualsIgnoreCase(s)
+            | Operator::StartsWithIgnoreCase(s)
+            | Operator::EndsWithIgnoreCase(s)
+            | Operator::ContainsIgnoreCase(s) => {
                 if let Value::String(str) = value {
-                    Ok(str.starts_with(s))
+                    let (str, s) = (str.to_lowercase(), s.to_lowercase());
+                    Ok(match self {
+                        Operator::EqualsIgnoreCase(_) => str == s,
+                        Operator::StartsWithIgnoreCase(_) => str.starts_with(&s),
+                        Operator::EndsWithIgnoreCase(_) => str.ends_with(&s),
+                        _ => str.contains(&s),
+                    })
                 } else {
                     Err(FilterError::TypeMismatch {
                         expected: "string".to_string(),
@@ -178,9 +1274,9 @@ impl Filter {
                 }
             }
 
-            Operator::EndsWith(s) => {
+            Operator::EqualsTrimmed(s) => {
                 if let Value::String(str) = value {
-                    Ok(str.ends_with(s))
+                    Ok(str.trim() == s.trim())
                 } else {
                     Err(FilterError::TypeMismatch {
                         expected: "string".to_string(),
@@ -189,9 +1285,16 @@ impl Filter {
                 }
             }
 
-            Operator::Contains(s) => {
+            #[cfg(feature = "regex")]
+            Operator::RegexCapture {
+                pattern,
+                group,
+                inner,
+            } => {
                 if let Value::String(str) = value {
-                    Ok(str.contains(s))
+                    let regex = regex::Regex::new(pattern)
+                        .map_err(|e| FilterError::InvalidRegex(e.to_string()))?;
+                    capture_matches(&regex, group, inner, str, scope, ctx)
                 } else {
                     Err(FilterError::TypeMismatch {
                         expected: "string".to_string(),
@@ -200,160 +1303,5653 @@ impl Filter {
                 }
             }
 
-            Operator::ArrayContains(target) => {
-                if let Value::Array(arr) = value {
-                    Ok(arr.contains(target))
+            #[cfg(feature = "regex")]
+            Operator::MatchesRegexCapture {
+                regex: CompiledRegex(regex),
+                group,
+                inner,
+            } => {
+                if let Value::String(str) = value {
+                    capture_matches(regex, group, inner, str, scope, ctx)
                 } else {
                     Err(FilterError::TypeMismatch {
-                        expected: "array".to_string(),
+                        expected: "string".to_string(),
                         got: format!("{:?}", value),
                     })
                 }
             }
 
-            Operator::HasKey(key) => {
-                if let Value::Object(obj) = value {
-                    Ok(obj.contains_key(key))
+            #[cfg(feature = "regex")]
+            Operator::Regex(pattern) => {
+                if let Value::String(str) = value {
+                    let regex = regex::Regex::new(pattern)
+                        .map_err(|e| FilterError::InvalidRegex(e.to_string()))?;
+                    Ok(regex.is_match(str))
                 } else {
                     Err(FilterError::TypeMismatch {
-                        expected: "object".to_string(),
+                        expected: "string".to_string(),
                         got: format!("{:?}", value),
                     })
                 }
             }
 
-            Operator::And(filters) => {
-                let mut results = Vec::new();
-                for filter in filters {
-                    results.push(filter.check(value)?);
+            #[cfg(feature = "regex")]
+            Operator::MatchesRegex(CompiledRegex(regex)) => {
+                if let Value::String(str) = value {
+                    Ok(regex.is_match(str))
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
                 }
-                Ok(results.iter().all(|&x| x))
             }
 
-            Operator::Or(filters) => {
-                let mut results = Vec::new();
-                for filter in filters {
-                    results.push(filter.check(value)?);
+            Operator::EqualsNormalizedWhitespace(s) => {
+                if let Value::String(str) = value {
+                    Ok(str.split_whitespace().eq(s.split_whitespace()))
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
                 }
-                Ok(results.iter().any(|&x| x))
             }
-        }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
+            Operator::Format(kind) => {
+                if let Value::String(str) = value {
+                    Ok(kind.matches(str))
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
 
-    #[test]
-    fn test_numeric_operators() {
-        let value = json!({ "age": 25 });
+            Operator::LooksLikeNumber | Operator::LooksLikeInteger => {
+                if let Value::String(str) = value {
+                    let str = str.trim();
+                    let is_integer = str.parse::<i64>().is_ok();
+                    Ok(match self {
+                        Operator::LooksLikeInteger => is_integer,
+                        _ => is_integer || str.parse::<f64>().is_ok_and(f64::is_finite),
+                    })
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
 
-        let filter = Filter::new("age", Operator::GreaterThan(20.0));
-        assert!(filter.check(&value).unwrap());
+            Operator::Luhn => {
+                if let Value::String(str) = value {
+                    Ok(luhn_valid(str))
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
 
-        let filter = Filter::new("age", Operator::LessThan(30.0));
-        assert!(filter.check(&value).unwrap());
+            Operator::FuzzyMatches {
+                target,
+                min_similarity,
+            } => {
+                if let Value::String(str) = value {
+                    Ok(similarity(str, target) >= *min_similarity)
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
+
+            #[cfg(feature = "phonetic")]
+            Operator::Phonetic { target, algorithm } => {
+                if let Value::String(str) = value {
+                    let encoded = algorithm.encode(str);
+                    Ok(!encoded.is_empty() && encoded == algorithm.encode(target))
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
+
+            #[cfg(feature = "diacritics")]
+            Operator::ContainsNormalized(s) => {
+                if let Value::String(str) = value {
+                    Ok(strip_diacritics(str).contains(&strip_diacritics(s)))
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
+
+            #[cfg(feature = "collation")]
+            Operator::CollatedEquals { target, locale } => {
+                if let Value::String(str) = value {
+                    Ok(collator(locale)?.compare(str, target) == Ordering::Equal)
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
+
+            #[cfg(feature = "hash")]
+            Operator::HashEquals {
+                algorithm,
+                expected,
+            } => {
+                if let Value::String(str) = value {
+                    Ok(algorithm.hex_digest(str).eq_ignore_ascii_case(expected))
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "string".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
+
+            Operator::ArrayContains(target) => {
+                if let Value::Array(arr) = value {
+                    Ok(arr.contains(target))
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "array".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
+
+            Operator::ArrayContainsAll(targets) => {
+                let arr = as_array(value)?;
+                Ok(targets.iter().all(|target| arr.contains(target)))
+            }
+
+            Operator::ArrayContainsAny(targets) => {
+                let arr = as_array(value)?;
+                Ok(targets.iter().any(|target| arr.contains(target)))
+            }
+
+            Operator::IsSubsetOf(superset) => Ok(as_array(value)?
+                .iter()
+                .all(|element| superset.iter().any(|v| values_equal(element, v)))),
+
+            Operator::DuplicateCount { op, count } => {
+                let arr = as_array(value)?;
+                let mut distinct: Vec<&Value> = Vec::with_capacity(arr.len());
+                for element in arr {
+                    if !distinct.iter().any(|seen| values_equal(seen, element)) {
+                        distinct.push(element);
+                    }
+                }
+                Ok(op.compare(arr.len() - distinct.len(), *count))
+            }
+
+            Operator::ArrayEqualsIgnoringIndices { target, ignore } => {
+                let arr = as_array(value)?;
+                Ok(arr.len() == target.len()
+                    && arr
+                        .iter()
+                        .zip(target)
+                        .enumerate()
+                        .all(|(index, (a, b))| ignore.contains(&index) || values_equal(a, b)))
+            }
+
+            Operator::Length(inner) => {
+                let length = match value {
+                    Value::String(str) => str.chars().count(),
+                    Value::Array(arr) => arr.len(),
+                    Value::Object(obj) => obj.len(),
+                    _ => {
+                        return Err(FilterError::TypeMismatch {
+                            expected: "string, array or object".to_string(),
+                            got: format!("{:?}", value),
+                        })
+                    }
+                };
+                inner.evaluate(&Value::from(length), scope, ctx)
+            }
+
+            Operator::Flatten { depth, inner } => {
+                let mut flattened = Vec::new();
+                flatten_into(as_array(value)?, *depth, &mut flattened);
+                inner.evaluate(&Value::Array(flattened), scope, ctx)
+            }
+
+            Operator::Window {
+                size,
+                inner,
+                quantifier,
+            } => {
+                let arr = as_array(value)?;
+                if *size == 0 || *size > arr.len() {
+                    return Ok(false);
+                }
+
+                for window in arr.windows(*size) {
+                    let matched =
+                        inner.eval(&Value::Array(window.to_vec()), Quantifier::Any, ctx)?;
+                    match quantifier {
+                        Quantifier::Any if matched => return Ok(true),
+                        Quantifier::All if !matched => return Ok(false),
+                        _ => {}
+                    }
+                }
+                Ok(*quantifier == Quantifier::All)
+            }
+
+            Operator::All(inner) => {
+                for element in as_array(value)? {
+                    if !inner.eval(element, Quantifier::Any, ctx)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+
+            Operator::Any(inner) => {
+                for element in as_array(value)? {
+                    if inner.eval(element, Quantifier::Any, ctx)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+
+            Operator::Exists(exists) => Ok(*exists),
+
+            Operator::TypeIs(json_type) => Ok(JsonType::of(value) == *json_type),
+
+            Operator::EntriesMatching {
+                key_glob,
+                inner,
+                quantifier,
+            } => {
+                let Value::Object(obj) = value else {
+                    return Err(FilterError::TypeMismatch {
+                        expected: "object".to_string(),
+                        got: format!("{:?}", value),
+                    });
+                };
+
+                for (_, entry) in obj.iter().filter(|(key, _)| glob_matches(key_glob, key)) {
+                    let matched = inner.eval(entry, Quantifier::Any, ctx)?;
+                    match quantifier {
+                        Quantifier::Any if matched => return Ok(true),
+                        Quantifier::All if !matched => return Ok(false),
+                        _ => {}
+                    }
+                }
+                Ok(*quantifier == Quantifier::All)
+            }
+
+            Operator::Fields {
+                fields,
+                missing_is_error,
+            } => {
+                let Value::Object(obj) = value else {
+                    return Err(FilterError::TypeMismatch {
+                        expected: "object".to_string(),
+                        got: format!("{:?}", value),
+                    });
+                };
+                for (key, operator) in fields {
+                    let matched = match obj.get(key) {
+                        Some(field) => operator.evaluate(field, scope, ctx)?,
+                        None => match operator.missing_result() {
+                            Some(result) => result,
+                            None if *missing_is_error => {
+                                return Err(FilterError::PathNotFound(key.to_string()))
+                            }
+                            None => false,
+                        },
+                    };
+                    if !matched {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+
+            Operator::HasKey(key) => {
+                if let Value::Object(obj) = value {
+                    Ok(obj.contains_key(key))
+                } else {
+                    Err(FilterError::TypeMismatch {
+                        expected: "object".to_string(),
+                        got: format!("{:?}", value),
+                    })
+                }
+            }
+
+            Operator::OptionalMatch(inner) => inner.evaluate(value, scope, ctx),
+
+            Operator::OrFalseOnMismatch(inner) => match inner.evaluate(value, scope, ctx) {
+                Err(FilterError::TypeMismatch { .. }) => Ok(false),
+                result => result,
+            },
+
+            Operator::And(filters) => {
+                for filter in filters {
+                    if !filter.eval(value, Quantifier::Any, ctx)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+
+            Operator::Or(filters) => {
+                for filter in filters {
+                    if filter.eval(value, Quantifier::Any, ctx)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+
+            Operator::Not(filter) => Ok(!filter.eval(value, Quantifier::Any, ctx)?),
+        }
+    }
+}
+
+/// Ways of combining numbers into one, as in [`Operator::AggregateEqualsPath`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Reducer {
+    Sum,
+    Min,
+    Max,
+    Average,
+    Count,
+}
+
+impl Reducer {
+    /// `None` when there's nothing to reduce and no neutral result.
+    fn reduce(self, numbers: &[f64]) -> Option<f64> {
+        match self {
+            Reducer::Sum => Some(numbers.iter().sum()),
+            Reducer::Min => numbers.iter().copied().reduce(f64::min),
+            Reducer::Max => numbers.iter().copied().reduce(f64::max),
+            Reducer::Average if numbers.is_empty() => None,
+            Reducer::Average => Some(numbers.iter().sum::<f64>() / numbers.len() as f64),
+            Reducer::Count => Some(numbers.len() as f64),
+        }
+    }
+}
+
+/// Sign classes checked by [`Operator::Sign`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Sign {
+    Positive,
+    Negative,
+    Zero,
+    NonNegative,
+    NonPositive,
+}
+
+/// Comparisons against a count, as in [`Operator::DuplicateCount`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl CompareOp {
+    fn compare<T: Ord>(self, a: T, b: T) -> bool {
+        match self {
+            CompareOp::Eq => a == b,
+            CompareOp::Ne => a != b,
+            CompareOp::Gt => a > b,
+            CompareOp::Ge => a >= b,
+            CompareOp::Lt => a < b,
+            CompareOp::Le => a <= b,
+        }
+    }
+}
+
+/// JSON value types checked by [`Operator::TypeIs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum JsonType {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl JsonType {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => JsonType::Null,
+            Value::Bool(_) => JsonType::Bool,
+            Value::Number(_) => JsonType::Number,
+            Value::String(_) => JsonType::String,
+            Value::Array(_) => JsonType::Array,
+            Value::Object(_) => JsonType::Object,
+        }
+    }
+}
+
+/// Well-known string formats checked by [`Operator::Format`].
+///
+/// The checks are intentionally lightweight: they validate the shape of the
+/// string, not e.g. whether an email domain exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum FormatKind {
+    Uuid,
+    Email,
+    Url,
+    Ipv4,
+    Ipv6,
+}
+
+impl FormatKind {
+    fn matches(self, s: &str) -> bool {
+        match self {
+            FormatKind::Uuid => {
+                let groups: Vec<&str> = s.split('-').collect();
+                groups.len() == 5
+                    && groups.iter().zip([8, 4, 4, 4, 12]).all(|(group, len)| {
+                        group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit())
+                    })
+            }
+            FormatKind::Email => match s.split_once('@') {
+                Some((local, domain)) => {
+                    !local.is_empty()
+                        && !domain.contains('@')
+                        && !s.chars().any(char::is_whitespace)
+                        && domain.split('.').count() >= 2
+                        && domain.split('.').all(|label| !label.is_empty())
+                }
+                None => false,
+            },
+            FormatKind::Url => match s.split_once("://") {
+                Some((scheme, rest)) => {
+                    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                        && scheme
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                        && !rest.is_empty()
+                        && !rest.starts_with('/')
+                        && !rest.chars().any(char::is_whitespace)
+                }
+                None => false,
+            },
+            FormatKind::Ipv4 => s.parse::<std::net::Ipv4Addr>().is_ok(),
+            FormatKind::Ipv6 => s.parse::<std::net::Ipv6Addr>().is_ok(),
+        }
+    }
+}
+
+/// Phonetic encodings supported by [`Operator::Phonetic`].
+#[cfg(feature = "phonetic")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum PhoneticAlgo {
+    /// American Soundex, e.g. both `Smith` and `Smyth` encode to `S530`.
+    Soundex,
+}
+
+#[cfg(feature = "phonetic")]
+impl PhoneticAlgo {
+    fn encode(self, s: &str) -> String {
+        match self {
+            PhoneticAlgo::Soundex => soundex(s),
+        }
+    }
+}
+
+/// Hash functions supported by [`Operator::HashEquals`].
+#[cfg(feature = "hash")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum HashAlgo {
+    Sha256,
+    Sha512,
+}
+
+#[cfg(feature = "hash")]
+impl HashAlgo {
+    fn hex_len(self) -> usize {
+        match self {
+            HashAlgo::Sha256 => 64,
+            HashAlgo::Sha512 => 128,
+        }
+    }
+
+    fn hex_digest(self, s: &str) -> String {
+        use sha2::Digest;
+
+        let digest = match self {
+            HashAlgo::Sha256 => sha2::Sha256::digest(s).to_vec(),
+            HashAlgo::Sha512 => sha2::Sha512::digest(s).to_vec(),
+        };
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// A capture group of [`Operator::RegexCapture`], serialized as the bare
+/// index or name.
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum CaptureGroup {
+    Index(usize),
+    Name(String),
+}
+
+#[cfg(feature = "regex")]
+impl CaptureGroup {
+    fn get<'h>(&self, captures: &regex::Captures<'h>) -> Option<regex::Match<'h>> {
+        match self {
+            CaptureGroup::Index(index) => captures.get(*index),
+            CaptureGroup::Name(name) => captures.name(name),
+        }
+    }
+
+    /// Fails unless `regex` has this group.
+    fn validate(&self, regex: &regex::Regex) -> Result<(), FilterError> {
+        let exists = match self {
+            CaptureGroup::Index(index) => *index < regex.captures_len(),
+            CaptureGroup::Name(name) => regex.capture_names().flatten().any(|n| n == name),
+        };
+        if exists {
+            Ok(())
+        } else {
+            Err(FilterError::InvalidRegex(format!(
+                "{} has no capture group {}",
+                regex.as_str(),
+                self
+            )))
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+impl std::fmt::Display for CaptureGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureGroup::Index(index) => write!(f, "{}", index),
+            CaptureGroup::Name(name) => write!(f, "`{}`", name),
+        }
+    }
+}
+
+/// A compiled regex held by [`Operator::MatchesRegex`]. Cloning shares the
+/// compiled program, and two regexes compare equal when their patterns do.
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone)]
+pub struct CompiledRegex(pub regex::Regex);
+
+#[cfg(feature = "regex")]
+impl PartialEq for CompiledRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/// Deduplicates path strings so that many filters referencing the same paths
+/// share a single allocation.
+#[derive(Debug, Default)]
+pub struct PathInterner {
+    paths: HashSet<Arc<str>>,
+}
+
+impl PathInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, path: &str) -> Arc<str> {
+        if let Some(interned) = self.paths.get(path) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = path.into();
+        self.paths.insert(interned.clone());
+        interned
+    }
+}
+
+/// A path that failed to resolve during [`Filter::dry_run`].
+#[derive(Debug)]
+pub struct PathIssue {
+    /// Full path from the document root, with nested filter paths joined on.
+    pub path: String,
+    pub error: FilterError,
+}
+
+/// Evaluation settings for [`Filter::check_with_options`].
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct FilterOptions {
+    /// Coercions applied to resolved values before the operator sees them,
+    /// keyed by the full path of the filter, with the paths of enclosing
+    /// filters joined on as in [`Filter::referenced_paths`]. So `age` nested
+    /// in a filter on `user` is coerced by a rule for `user.age`, not `age`.
+    #[serde(default)]
+    pub coercions: HashMap<String, CoercionKind>,
+    /// Evaluates container operators (such as `ArrayContains`, `HasKey` or
+    /// `Length`) whose path doesn't resolve against an empty array or object
+    /// instead of failing with `PathNotFound`.
+    #[serde(default)]
+    pub missing_containers_empty: bool,
+    /// Makes `Equals`, `NotEqual` and `In` treat `true` as equal to the number
+    /// `1` and `false` to `0` (including `1.0` and `0.0`), for sources that
+    /// serialize booleans inconsistently.
+    #[serde(default)]
+    pub bools_as_numbers: bool,
+}
+
+/// A conversion applied to a resolved value. Values that can't be converted
+/// are passed through unchanged, so the operator still reports a type mismatch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum CoercionKind {
+    /// Numeric strings (`"42"`, `" 4.2 "`) and booleans (as `1` / `0`).
+    Number,
+    /// `"true"` / `"false"` strings and the numbers `1` / `0`.
+    Bool,
+    /// Numbers and booleans in their JSON text form.
+    String,
+}
+
+impl CoercionKind {
+    fn coerce(self, value: &Value) -> Value {
+        let coerced = match (self, value) {
+            (CoercionKind::Number, Value::String(s)) => {
+                let s = s.trim();
+                s.parse::<i64>()
+                    .map(Value::from)
+                    .ok()
+                    .or_else(|| serde_json::Number::from_f64(s.parse().ok()?).map(Value::Number))
+            }
+            (CoercionKind::Number, Value::Bool(b)) => Some(Value::from(*b as u8)),
+            (CoercionKind::Bool, Value::String(s)) => match s.trim() {
+                "true" => Some(Value::Bool(true)),
+                "false" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            (CoercionKind::Bool, Value::Number(_)) => match as_number(value) {
+                Some(NumRepr::Int(1)) => Some(Value::Bool(true)),
+                Some(NumRepr::Int(0)) => Some(Value::Bool(false)),
+                _ => None,
+            },
+            (CoercionKind::String, Value::Number(_) | Value::Bool(_)) => {
+                Some(Value::String(value.to_string()))
+            }
+            _ => None,
+        };
+        coerced.unwrap_or_else(|| value.clone())
+    }
+}
+
+type Observer<'a> = dyn FnMut(&str, Result<&Value, &FilterError>) + 'a;
+
+/// Settings for a single evaluation, threaded through nested filters.
+#[derive(Default)]
+struct Context<'a> {
+    deadline: Option<Instant>,
+    options: Option<&'a FilterOptions>,
+    observer: Option<RefCell<&'a mut Observer<'a>>>,
+    /// The full path and operator name of the innermost filter that failed,
+    /// only tracked by [`Filter::check_detailed`].
+    failure: Option<RefCell<Option<(String, String)>>>,
+    /// Paths of the filters enclosing the one being evaluated, only tracked
+    /// while observing or tracking failures.
+    parents: RefCell<Vec<Arc<str>>>,
+    /// Whether errors caused by the document's shape count as non-matches, see
+    /// [`Filter::check_lenient`].
+    lenient: bool,
+}
+
+impl Context<'_> {
+    fn tracks_parents(&self) -> bool {
+        self.observer.is_some() || self.failure.is_some() || self.coerces()
+    }
+
+    fn coerces(&self) -> bool {
+        self.options
+            .is_some_and(|options| !options.coercions.is_empty())
+    }
+
+    fn full_path(&self, filter: &Filter) -> String {
+        let path = self
+            .parents
+            .borrow()
+            .iter()
+            .fold(".".to_string(), |path, parent| nested_path(&path, parent));
+        nested_path(&path, &filter.path)
+    }
+
+    fn observe(&self, filter: &Filter, result: Result<&Value, &FilterError>) {
+        if let Some(observer) = &self.observer {
+            (observer.borrow_mut())(&self.full_path(filter), result);
+        }
+    }
+
+    /// Records `filter` as the source of `error` unless a filter nested inside
+    /// it already was, then hands the error back for propagation.
+    fn fail(&self, filter: &Filter, error: FilterError) -> FilterError {
+        if let Some(failure) = &self.failure {
+            failure.borrow_mut().get_or_insert_with(|| {
+                (self.full_path(filter), filter.operator.name().to_string())
+            });
+        }
+        error
+    }
+
+    /// [`values_equal`], widened by [`FilterOptions::bools_as_numbers`].
+    fn values_equal(&self, a: &Value, b: &Value) -> bool {
+        let widen = self.options.is_some_and(|options| options.bools_as_numbers);
+        match (a, b) {
+            (Value::Bool(bool), number @ Value::Number(_))
+            | (number @ Value::Number(_), Value::Bool(bool))
+                if widen =>
+            {
+                as_number(number) == Some(NumRepr::Int(i64::from(*bool)))
+            }
+            (a, b) => values_equal(a, b),
+        }
+    }
+
+    fn recovers(&self, error: &FilterError) -> bool {
+        self.lenient
+            && matches!(
+                error,
+                FilterError::PathNotFound(_)
+                    | FilterError::InvalidArrayIndex(_)
+                    | FilterError::TypeMismatch { .. }
+            )
+    }
+
+    fn checkpoint(&self) -> Result<(), FilterError> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(FilterError::Timeout),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// How an operator is combined over the branches a path resolves to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Quantifier {
+    Any,
+    All,
+}
+
+#[derive(Error, Debug)]
+pub enum FilterError {
+    #[error("Path not found: {0}")]
+    PathNotFound(String),
+
+    #[error("Type mismatch: expected {expected}, got {got}")]
+    TypeMismatch { expected: String, got: String },
+
+    #[error("Invalid array index in path: {0}")]
+    InvalidArrayIndex(String),
+
+    #[error("Invalid path format: {0}")]
+    InvalidPath(String),
+
+    #[error("Filter evaluation timed out")]
+    Timeout,
+
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+
+    #[error("Invalid operand: {0}")]
+    InvalidOperand(String),
+
+    #[error("Invalid number: {0}")]
+    InvalidNumber(String),
+
+    #[cfg(feature = "byte-size")]
+    #[error("Invalid byte size: {0}")]
+    InvalidByteSize(String),
+
+    #[cfg(feature = "datetime")]
+    #[error("Invalid timestamp: {0}")]
+    InvalidTimestamp(String),
+
+    #[cfg(feature = "regex")]
+    #[error("Invalid regex: {0}")]
+    InvalidRegex(String),
+
+    #[cfg(feature = "collation")]
+    #[error("Invalid locale: {0}")]
+    InvalidLocale(String),
+}
+
+/// Errors from helpers that obtain the document themselves before filtering it.
+#[derive(Error, Debug)]
+pub enum CheckError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid JSON: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    #[cfg(feature = "cbor")]
+    #[error("Invalid CBOR: {0}")]
+    Cbor(#[from] ciborium::de::Error<std::io::Error>),
+
+    #[error(transparent)]
+    Filter(#[from] FilterError),
+}
+
+/// A [`FilterError`] along with the filter it came from, see
+/// [`Filter::check_detailed`].
+#[derive(Error, Debug)]
+#[error("{operator} at {path}: {error}")]
+pub struct DetailedError {
+    /// Full path of the failing filter, with nested filter paths joined on.
+    pub path: String,
+    /// Name of the failing filter's operator, e.g. `GreaterThan`.
+    pub operator: String,
+    #[source]
+    pub error: FilterError,
+}
+
+impl Filter {
+    pub fn new(path: impl Into<String>, operator: Operator) -> Self {
+        Self {
+            path: path.into().into(),
+            operator,
+        }
+    }
+
+    /// Like `new`, but rejects filters that would fail on every check, such as
+    /// malformed paths, NaN bounds or invalid regexes, see [`Filter::validate`].
+    pub fn try_new(path: impl Into<String>, operator: Operator) -> Result<Self, FilterError> {
+        let filter = Self::new(path, operator);
+        filter.validate()?;
+        Ok(filter)
+    }
+
+    /// Checks the parts of this filter that don't depend on the document: path
+    /// syntax (including paths referenced by operators such as `InPath`), NaN
+    /// numeric operands, and, with the corresponding features, regexes, byte
+    /// sizes and locales. Nested filters are validated too.
+    pub fn validate(&self) -> Result<(), FilterError> {
+        parse_path(&self.path)?;
+        self.operator.validate()?;
+        for filter in self.operator.subfilters() {
+            filter.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Creates a filter sharing an already allocated path, typically one
+    /// returned by [`PathInterner::intern`].
+    pub fn new_interned(path: Arc<str>, operator: Operator) -> Self {
+        Self { path, operator }
+    }
+
+    /// Returns a hash of this filter suitable for use as a cache key.
+    ///
+    /// The key is stable for a given build of this crate. Children of `And` / `Or`
+    /// are hashed in order, so filters that only differ in the order of their
+    /// clauses produce different keys; sort the clauses first if that matters.
+    pub fn hash_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Checks the filter against `value`. When the path fans out (`*`, `[]` or
+    /// `[?key=value]`), the filter passes if the operator matches any selected
+    /// value, and a path that selects nothing (e.g. `*` over an empty array)
+    /// evaluates to `false` rather than an error.
+    pub fn check(&self, value: &Value) -> Result<bool, FilterError> {
+        self.check_quantified(value, Quantifier::Any)
+    }
+
+    /// Like `check`, but gives up with [`FilterError::Timeout`] once `deadline`
+    /// has passed. The deadline is checked before evaluating each filter node, so
+    /// a single slow operator can overrun it by its own evaluation time.
+    pub fn check_with_deadline(
+        &self,
+        value: &Value,
+        deadline: Instant,
+    ) -> Result<bool, FilterError> {
+        let ctx = Context {
+            deadline: Some(deadline),
+            ..Context::default()
+        };
+        self.eval(value, Quantifier::Any, &ctx)
+    }
+
+    /// Like `check`, additionally calling `observer` with the full path and the
+    /// resolved value (or resolution error) of each leaf filter as it's
+    /// evaluated. Cheaper than building a full trace, so suitable for telemetry.
+    pub fn check_observed(
+        &self,
+        value: &Value,
+        mut observer: impl FnMut(&str, Result<&Value, &FilterError>),
+    ) -> Result<bool, FilterError> {
+        let ctx = Context {
+            observer: Some(RefCell::new(&mut observer)),
+            ..Context::default()
+        };
+        self.eval(value, Quantifier::Any, &ctx)
+    }
+
+    /// Evaluates the filter like `check`, returning a tree mirroring the filter
+    /// with the result (or error) of every node, see [`EvalTrace`]. Every child
+    /// of `And` and `Or` is evaluated, so all failing clauses show up.
+    pub fn explain(&self, value: &Value) -> EvalTrace {
+        self.explain_node(value, &Context::default())
+    }
+
+    /// [`Filter::explain`] as JSON, for debugging UIs. Each node has the
+    /// filter's `path`, `operator` name, `result` (`null` on error, with the
+    /// message in `error`) and the `branches` its path resolved to, each with
+    /// its concrete `path` (relative to the node's input), a `value` preview and
+    /// `result`, plus `children` nodes for `And`, `Or` and `Not`.
+    pub fn explain_json(&self, value: &Value) -> Value {
+        serde_json::to_value(self.explain(value)).expect("traces serialize to JSON")
+    }
+
+    fn explain_node(&self, value: &Value, ctx: &Context) -> EvalTrace {
+        let mut branches = Vec::new();
+        let result = match (self.resolve_branches(value), self.operator.missing_result()) {
+            (Err(FilterError::PathNotFound(_)), Some(result)) => Ok(result),
+            (Err(error), _) => Err(error.to_string()),
+            (Ok(resolved), _) => {
+                let mut result = Ok(false);
+                for (path, target) in resolved {
+                    let mut children = Vec::new();
+                    let matched = match &self.operator {
+                        Operator::And(filters) | Operator::Or(filters) if !filters.is_empty() => {
+                            // Every child is traced, but combined in order like
+                            // `check`: the first error or deciding result wins
+                            let decisive = matches!(self.operator, Operator::Or(_));
+                            let mut matched = None;
+                            for filter in filters {
+                                let child = filter.explain_node(&target, ctx);
+                                match child.outcome() {
+                                    Ok(result) if result != decisive => {}
+                                    outcome => {
+                                        matched.get_or_insert(outcome);
+                                    }
+                                }
+                                children.push(child);
+                            }
+                            matched.unwrap_or(Ok(!decisive))
+                        }
+                        Operator::Not(filter) => {
+                            let child = filter.explain_node(&target, ctx);
+                            let matched = child.outcome().map(|matched| !matched);
+                            children.push(child);
+                            matched
+                        }
+                        operator => operator
+                            .evaluate(&target, value, ctx)
+                            .map_err(|error| error.to_string()),
+                    };
+
+                    branches.push(TraceBranch {
+                        path,
+                        value: preview(&target),
+                        result: matched.as_ref().ok().copied(),
+                        children,
+                    });
+
+                    result = matched;
+                    if result != Ok(false) {
+                        break;
+                    }
+                }
+                result
+            }
+        };
+
+        EvalTrace {
+            path: self.path.to_string(),
+            operator: self.operator.name().to_string(),
+            result: result.as_ref().ok().copied(),
+            error: result.err(),
+            branches,
+        }
+    }
+
+    /// Like `check`, but on failure also reports the full path and operator
+    /// name of the innermost filter that failed, for logging.
+    pub fn check_detailed(&self, value: &Value) -> Result<bool, DetailedError> {
+        let ctx = Context {
+            failure: Some(RefCell::new(None)),
+            ..Context::default()
+        };
+        self.eval(value, Quantifier::Any, &ctx).map_err(|error| {
+            let failure = ctx.failure.and_then(RefCell::into_inner);
+            let (path, operator) = failure
+                .unwrap_or_else(|| (self.path.to_string(), self.operator.name().to_string()));
+            DetailedError {
+                path,
+                operator,
+                error,
+            }
+        })
+    }
+
+    /// Like `check`, but a filter whose path doesn't resolve (`PathNotFound`,
+    /// `InvalidArrayIndex`) or whose operator gets the wrong type
+    /// (`TypeMismatch`) evaluates to `false`, including inside `And`/`Or`. Useful
+    /// across documents of varying shape. Errors in the filter itself, such as an
+    /// invalid regex, are still returned.
+    pub fn check_lenient(&self, value: &Value) -> Result<bool, FilterError> {
+        let ctx = Context {
+            lenient: true,
+            ..Context::default()
+        };
+        self.eval(value, Quantifier::Any, &ctx)
+    }
+
+    /// Reads and parses the JSON file at `path`, then checks it.
+    pub fn check_against_file(&self, path: &Path) -> Result<bool, CheckError> {
+        let value: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        Ok(self.check(&value)?)
+    }
+
+    /// Serializes `value` (e.g. a struct deriving `Serialize`) to JSON, then
+    /// checks it.
+    pub fn check_serializable<T: Serialize>(&self, value: &T) -> Result<bool, CheckError> {
+        Ok(self.check(&serde_json::to_value(value)?)?)
+    }
+
+    /// Decodes a CBOR document and checks it like the equivalent JSON. Documents
+    /// JSON can't represent, such as maps with non-string keys, fail to decode.
+    #[cfg(feature = "cbor")]
+    pub fn check_cbor(&self, bytes: &[u8]) -> Result<bool, CheckError> {
+        let value: Value = ciborium::de::from_reader(bytes)?;
+        Ok(self.check(&value)?)
+    }
+
+    /// Copies newline-delimited JSON from `reader` to `writer`, adding a boolean
+    /// `field` to every record that says whether it matched. Records the filter
+    /// fails on are annotated with `null`, and lines that aren't JSON objects are
+    /// copied unchanged. Returns the number of matching records.
+    pub fn annotate_ndjson<R: BufRead, W: Write>(
+        &self,
+        reader: R,
+        mut writer: W,
+        field: &str,
+    ) -> io::Result<usize> {
+        let mut matched = 0;
+
+        for line in reader.lines() {
+            let line = line?;
+            let mut record = match serde_json::from_str::<Value>(&line) {
+                Ok(record) if record.is_object() => record,
+                _ => {
+                    writeln!(writer, "{}", line)?;
+                    continue;
+                }
+            };
+
+            let annotation = match self.check(&record) {
+                Ok(true) => {
+                    matched += 1;
+                    Value::Bool(true)
+                }
+                Ok(false) => Value::Bool(false),
+                Err(_) => Value::Null,
+            };
+            if let Value::Object(obj) = &mut record {
+                obj.insert(field.to_string(), annotation);
+            }
+
+            serde_json::to_writer(&mut writer, &record)?;
+            writeln!(writer)?;
+        }
+
+        Ok(matched)
+    }
+
+    /// Like `check`, with evaluation tweaked by `options`.
+    pub fn check_with_options(
+        &self,
+        value: &Value,
+        options: &FilterOptions,
+    ) -> Result<bool, FilterError> {
+        let ctx = Context {
+            options: Some(options),
+            ..Context::default()
+        };
+        self.eval(value, Quantifier::Any, &ctx)
+    }
+
+    /// Checks the operator against every branch the path resolves to.
+    ///
+    /// Paths without `*` or `[?key=value]` segments resolve to a single branch,
+    /// in which case both quantifiers behave like `check`. When no branch is selected,
+    /// `Any` yields `false` and `All` yields `true`.
+    pub fn check_quantified(
+        &self,
+        value: &Value,
+        quantifier: Quantifier,
+    ) -> Result<bool, FilterError> {
+        self.eval(value, quantifier, &Context::default())
+    }
+
+    /// Evaluates the filter with an explicit stack of [`Frame`]s rather than
+    /// recursing into `And`, `Or` and `Not`, so arbitrarily deep logical nesting
+    /// can't overflow the call stack.
+    fn eval(
+        &self,
+        value: &Value,
+        quantifier: Quantifier,
+        ctx: &Context,
+    ) -> Result<bool, FilterError> {
+        let parents = ctx.parents.borrow().len();
+        let result = Frame::start(self, Cow::Borrowed(value), quantifier, ctx)
+            .and_then(|frame| Frame::run(vec![frame], ctx));
+        if result.is_err() {
+            ctx.parents.borrow_mut().truncate(parents);
+        }
+        result
+    }
+
+    /// Returns the concrete paths (e.g. `items[2].price`) of every branch on
+    /// which the operator matched.
+    pub fn matching_paths(&self, value: &Value) -> Result<Vec<String>, FilterError> {
+        let mut paths = Vec::new();
+        for (path, target) in self.resolve_branches(value)? {
+            if self
+                .operator
+                .evaluate(&target, value, &Context::default())?
+            {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Counts the elements of the array at `array_path` that `element_filter`
+    /// matches.
+    ///
+    /// `array_path` is resolved relative to the value at this filter's path,
+    /// whose operator isn't applied, so a filter on `.` counts over
+    /// `array_path` from the root. The element filter's path is resolved
+    /// relative to each element, so `.` refers to the element itself.
+    pub fn count_matches(
+        &self,
+        value: &Value,
+        array_path: &str,
+        element_filter: &Filter,
+    ) -> Result<usize, FilterError> {
+        let mut count = 0;
+        for (_, scope) in self.resolve_branches(value)? {
+            for (_, target) in resolve_branches(array_path, &scope)? {
+                if let Value::Array(arr) = &*target {
+                    for element in arr {
+                        if element_filter.check(element)? {
+                            count += 1;
+                        }
+                    }
+                } else {
+                    return Err(FilterError::TypeMismatch {
+                        expected: "array".to_string(),
+                        got: format!("{:?}", target),
+                    });
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Clones the items that match. Items the filter fails on are skipped, see
+    /// `try_retain_matching` to get the error instead.
+    pub fn retain_matching(&self, items: &[Value]) -> Vec<Value> {
+        items
+            .iter()
+            .filter(|item| matches!(self.check(item), Ok(true)))
+            .cloned()
+            .collect()
+    }
+
+    /// Like `retain_matching`, but fails with the first error.
+    pub fn try_retain_matching(&self, items: &[Value]) -> Result<Vec<Value>, FilterError> {
+        let mut retained = Vec::new();
+        for item in items {
+            if self.check(item)? {
+                retained.push(item.clone());
+            }
+        }
+        Ok(retained)
+    }
+
+    /// A filter matching strings at `path` against an already compiled `regex`,
+    /// which avoids recompiling the pattern and can be shared between filters.
+    /// Unlike other filters, the result can't be serialized.
+    #[cfg(feature = "regex")]
+    pub fn regex_field(path: impl Into<String>, regex: regex::Regex) -> Self {
+        Self::new(path, Operator::MatchesRegex(CompiledRegex(regex)))
+    }
+
+    /// Checks whether this filter matches at least one element of `array`, for
+    /// documents whose root is an array of records. Errors on non-array input.
+    pub fn matches_any_element(&self, array: &Value) -> Result<bool, FilterError> {
+        for element in as_array(array)? {
+            if self.check(element)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Checks whether this filter matches every element of `array`. An empty
+    /// array passes. Errors on non-array input.
+    pub fn matches_all_elements(&self, array: &Value) -> Result<bool, FilterError> {
+        for element in as_array(array)? {
+            if !self.check(element)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Scores how well `value` matches this filter, from `0.0` to `1.0`, for
+    /// ranking rather than filtering. Most operators score `1.0` when they
+    /// pass and `0.0` otherwise, `FuzzyMatches` scores its similarity, `And`
+    /// takes the minimum of its children and `Or` the maximum. Like
+    /// [`Filter::check`], the best of several branches counts.
+    pub fn score(&self, value: &Value) -> Result<f64, FilterError> {
+        let branches = match (self.resolve_branches(value), self.operator.missing_result()) {
+            (Ok(branches), _) => branches,
+            (Err(FilterError::PathNotFound(_)), Some(result)) => {
+                return Ok(if result { 1.0 } else { 0.0 })
+            }
+            (Err(error), _) => return Err(error),
+        };
+
+        let mut best = 0.0;
+        for (_, target) in branches {
+            best = self.operator.score(&target, value)?.max(best);
+        }
+        Ok(best)
+    }
+
+    /// Rewrites negations into a simpler equivalent form: `Not(Not(x))` becomes
+    /// `x`, and `Not(And(..))` / `Not(Or(..))` become `Or` / `And` of the negated
+    /// children when that leaves fewer `Not`s, e.g. `Not(And([Not(a), Not(b)]))`
+    /// becomes `Or([a, b])`. Only negations of `.` filters are rewritten, since
+    /// other paths change what the nested filter is evaluated against.
+    pub fn simplify(self) -> Filter {
+        let operator = match self.operator {
+            Operator::And(filters) => {
+                Operator::And(filters.into_iter().map(Filter::simplify).collect())
+            }
+            Operator::Or(filters) => {
+                Operator::Or(filters.into_iter().map(Filter::simplify).collect())
+            }
+            Operator::Window {
+                size,
+                inner,
+                quantifier,
+            } => Operator::Window {
+                size,
+                inner: Box::new(inner.simplify()),
+                quantifier,
+            },
+            Operator::EntriesMatching {
+                key_glob,
+                inner,
+                quantifier,
+            } => Operator::EntriesMatching {
+                key_glob,
+                inner: Box::new(inner.simplify()),
+                quantifier,
+            },
+            Operator::All(inner) => Operator::All(Box::new(inner.simplify())),
+            Operator::Any(inner) => Operator::Any(Box::new(inner.simplify())),
+            Operator::Not(inner) => return Filter::simplify_not(self.path, inner.simplify()),
+            operator => operator,
+        };
+        Filter {
+            path: self.path,
+            operator,
+        }
+    }
+
+    /// Simplifies `Not(inner)` at `path`, with `inner` already simplified.
+    fn simplify_not(path: Arc<str>, inner: Filter) -> Filter {
+        let is_negation =
+            |filter: &Filter| &*filter.path == "." && matches!(filter.operator, Operator::Not(_));
+        let negate = |filter: Filter| match filter.operator {
+            Operator::Not(inner) if &*filter.path == "." => *inner,
+            _ => Filter::new(".", Operator::Not(Box::new(filter))),
+        };
+
+        if &*inner.path != "." {
+            return Filter::new_interned(path, Operator::Not(Box::new(inner)));
+        }
+        let beneficial = |filters: &[Filter]| {
+            filters.len() <= 2 * filters.iter().filter(|f| is_negation(f)).count()
+        };
+        match inner.operator {
+            Operator::Not(inner) if &*path == "." => *inner,
+            Operator::And(filters) if beneficial(&filters) => Filter::new_interned(
+                path,
+                Operator::Or(filters.into_iter().map(negate).collect()),
+            ),
+            Operator::Or(filters) if beneficial(&filters) => Filter::new_interned(
+                path,
+                Operator::And(filters.into_iter().map(negate).collect()),
+            ),
+            operator => Filter::new_interned(
+                path,
+                Operator::Not(Box::new(Filter::new_interned(inner.path, operator))),
+            ),
+        }
+    }
+
+    /// Prepares this filter for repeated evaluation, see [`CompiledFilter`].
+    pub fn compile(&self) -> CompiledFilter {
+        CompiledFilter::new(self)
+    }
+
+    /// Returns the full path of every leaf filter, i.e. every filter that isn't
+    /// just combining others, in the order they appear and without duplicates.
+    /// Paths of nested filters are joined onto their parents', so `user` with
+    /// an `Or` of `name` and `email` yields `user.name` and `user.email`.
+    ///
+    /// Paths that operators read besides their own follow the filter's path,
+    /// joined on the same way: `status` with `InPath("valid")` under `order`
+    /// yields `order.status` and `order.valid`, and the fields of `Fields` or
+    /// the coordinates of `WithinDistance` are joined onto the filter's path.
+    pub fn referenced_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        self.referenced_paths_into(".", &mut paths);
+        paths
+    }
+
+    fn referenced_paths_into(&self, parent: &str, paths: &mut Vec<String>) {
+        let path = nested_path(parent, &self.path);
+        let subfilters = self.operator.subfilters();
+        let mut push = |path: String| {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        };
+        if subfilters.is_empty() {
+            push(path.clone());
+        }
+        for operand in self.operator.operand_paths() {
+            match operand {
+                OperandPath::Scoped(operand) => push(nested_path(parent, &operand)),
+                OperandPath::Relative(operand) => push(nested_path(&path, &operand)),
+            }
+        }
+        for filter in subfilters {
+            filter.referenced_paths_into(&path, paths);
+        }
+    }
+
+    /// Returns the values this filter looks at, keyed by the full path of each
+    /// leaf filter. Paths that don't resolve map to `null`, and paths selecting
+    /// zero or several array elements map to an array of the selected values.
+    pub fn project(&self, value: &Value) -> Map<String, Value> {
+        let mut projection = Map::new();
+        self.project_into(Some(value), ".", &mut projection);
+        projection
+    }
+
+    fn project_into(
+        &self,
+        value: Option<&Value>,
+        parent: &str,
+        projection: &mut Map<String, Value>,
+    ) {
+        let path = nested_path(parent, &self.path);
+        let branches = value.and_then(|value| self.resolve_branches(value).ok());
+
+        let subfilters = self.operator.subfilters();
+        if subfilters.is_empty() {
+            let projected = match branches.as_deref() {
+                None => Value::Null,
+                Some([(_, target)]) => target.clone().into_owned(),
+                Some(branches) => Value::Array(
+                    branches
+                        .iter()
+                        .map(|(_, v)| v.clone().into_owned())
+                        .collect(),
+                ),
+            };
+            projection.entry(path).or_insert(projected);
+        } else {
+            match branches {
+                Some(branches) => {
+                    for (_, target) in branches {
+                        for filter in subfilters {
+                            filter.project_into(Some(&target), &path, projection);
+                        }
+                    }
+                }
+                None => {
+                    for filter in subfilters {
+                        filter.project_into(None, &path, projection);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Resolves every path in the filter tree against `sample` without evaluating
+    /// any operators, reporting the paths that can't be resolved.
+    ///
+    /// Useful to catch typos in paths before running a filter at scale.
+    pub fn dry_run(&self, sample: &Value) -> Vec<PathIssue> {
+        let mut issues = Vec::new();
+        self.dry_run_into(sample, ".", &mut issues);
+        issues
+    }
+
+    fn dry_run_into(&self, sample: &Value, parent: &str, issues: &mut Vec<PathIssue>) {
+        let path = nested_path(parent, &self.path);
+
+        match self.resolve_branches(sample) {
+            Ok(branches) => {
+                for (_, target) in branches {
+                    for filter in self.operator.subfilters() {
+                        filter.dry_run_into(&target, &path, issues);
+                    }
+                }
+            }
+            Err(error) => {
+                if !issues.iter().any(|issue| issue.path == path) {
+                    issues.push(PathIssue { path, error });
+                }
+            }
+        }
+    }
+
+    fn resolve_branches<'a>(&self, value: &'a Value) -> Result<Vec<Branch<'a>>, FilterError> {
+        resolve_branches(&self.path, value)
+    }
+}
+
+/// The evaluation of one filter, as returned by [`Filter::explain`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvalTrace {
+    /// The filter's own path, relative to its parent.
+    pub path: String,
+    /// Name of the filter's operator, e.g. `GreaterThan`.
+    pub operator: String,
+    /// `None` when evaluation failed with `error`.
+    pub result: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The values the path resolved to. Like `check`, branches after the first
+    /// match aren't evaluated.
+    pub branches: Vec<TraceBranch>,
+}
+
+impl EvalTrace {
+    fn outcome(&self) -> Result<bool, String> {
+        match (&self.result, &self.error) {
+            (Some(result), _) => Ok(*result),
+            (None, error) => Err(error.clone().unwrap_or_default()),
+        }
+    }
+}
+
+/// One value a traced filter's path resolved to, see [`EvalTrace`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceBranch {
+    /// Concrete path of the value, relative to the filter's input.
+    pub path: String,
+    /// The value as JSON, truncated to 64 characters.
+    pub value: String,
+    pub result: Option<bool>,
+    /// Traces of the nested filters of `And`, `Or` and `Not`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<EvalTrace>,
+}
+
+/// Fluent construction of [`Filter`]s, e.g.
+/// `FilterBuilder::path("age").greater_than(20.0).and(FilterBuilder::path("name").starts_with("John")).build()`.
+///
+/// The result is a plain `Filter`, identical to one written out by hand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterBuilder {
+    filter: Filter,
+}
+
+/// A path waiting for its operator, see [`FilterBuilder::path`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathBuilder {
+    path: String,
+}
+
+impl FilterBuilder {
+    pub fn path(path: impl Into<String>) -> PathBuilder {
+        PathBuilder { path: path.into() }
+    }
+
+    /// Requires both filters to match. Chained calls add to the same `And`.
+    pub fn and(self, other: FilterBuilder) -> Self {
+        self.combine(other, Operator::And, |operator| match operator {
+            Operator::And(filters) => Some(filters),
+            _ => None,
+        })
+    }
+
+    /// Requires either filter to match. Chained calls add to the same `Or`.
+    pub fn or(self, other: FilterBuilder) -> Self {
+        self.combine(other, Operator::Or, |operator| match operator {
+            Operator::Or(filters) => Some(filters),
+            _ => None,
+        })
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Self {
+            filter: Filter::new(".", Operator::Not(Box::new(self.filter))),
+        }
+    }
+
+    pub fn build(self) -> Filter {
+        self.filter
+    }
+
+    fn combine(
+        self,
+        other: FilterBuilder,
+        operator: fn(Vec<Filter>) -> Operator,
+        filters: fn(&mut Operator) -> Option<&mut Vec<Filter>>,
+    ) -> Self {
+        let mut filter = self.filter;
+        if &*filter.path == "." {
+            if let Some(filters) = filters(&mut filter.operator) {
+                filters.push(other.filter);
+                return Self { filter };
+            }
+        }
+        Self {
+            filter: Filter::new(".", operator(vec![filter, other.filter])),
+        }
+    }
+}
+
+impl PathBuilder {
+    /// Applies any operator, for those without a dedicated method.
+    pub fn operator(self, operator: Operator) -> FilterBuilder {
+        FilterBuilder {
+            filter: Filter::new(self.path, operator),
+        }
+    }
+
+    pub fn greater_than(self, n: f64) -> FilterBuilder {
+        self.operator(Operator::GreaterThan(n))
+    }
+
+    pub fn less_than(self, n: f64) -> FilterBuilder {
+        self.operator(Operator::LessThan(n))
+    }
+
+    pub fn greater_or_equal(self, n: f64) -> FilterBuilder {
+        self.operator(Operator::GreaterOrEqual(n))
+    }
+
+    pub fn less_or_equal(self, n: f64) -> FilterBuilder {
+        self.operator(Operator::LessOrEqual(n))
+    }
+
+    pub fn equals(self, value: impl Into<Value>) -> FilterBuilder {
+        self.operator(Operator::Equals(value.into()))
+    }
+
+    pub fn not_equal(self, value: impl Into<Value>) -> FilterBuilder {
+        self.operator(Operator::NotEqual(value.into()))
+    }
+
+    pub fn one_of(self, values: Vec<Value>) -> FilterBuilder {
+        self.operator(Operator::In(values))
+    }
+
+    pub fn starts_with(self, s: impl Into<String>) -> FilterBuilder {
+        self.operator(Operator::StartsWith(s.into()))
+    }
+
+    pub fn ends_with(self, s: impl Into<String>) -> FilterBuilder {
+        self.operator(Operator::EndsWith(s.into()))
+    }
+
+    pub fn contains(self, s: impl Into<String>) -> FilterBuilder {
+        self.operator(Operator::Contains(s.into()))
+    }
+
+    pub fn array_contains(self, value: impl Into<Value>) -> FilterBuilder {
+        self.operator(Operator::ArrayContains(value.into()))
+    }
+
+    pub fn has_key(self, key: impl Into<String>) -> FilterBuilder {
+        self.operator(Operator::HasKey(key.into()))
+    }
+
+    pub fn exists(self) -> FilterBuilder {
+        self.operator(Operator::Exists(true))
+    }
+}
+
+/// A filter being evaluated by [`Filter::eval`] against `value`, one branch of
+/// its path at a time.
+struct Frame<'a> {
+    filter: &'a Filter,
+    value: Cow<'a, Value>,
+    quantifier: Quantifier,
+    branches: std::vec::IntoIter<Branch<'a>>,
+    /// The result once every branch was checked without deciding the quantifier.
+    exhausted: bool,
+    /// The logical operator in progress on the current branch, if any.
+    logical: Option<Logical<'a>>,
+}
+
+/// An `And`, `Or` or `Not` whose children are evaluated as frames of their own.
+struct Logical<'a> {
+    target: Cow<'a, Value>,
+    next: usize,
+    matched: bool,
+}
+
+impl<'a> Frame<'a> {
+    fn start(
+        filter: &'a Filter,
+        value: Cow<'a, Value>,
+        quantifier: Quantifier,
+        ctx: &Context,
+    ) -> Result<Self, FilterError> {
+        ctx.checkpoint().map_err(|error| ctx.fail(filter, error))?;
+
+        let resolved = match &value {
+            Cow::Borrowed(value) => filter.resolve_branches(value),
+            // Children of computed values can't borrow from a frame, so they own theirs
+            Cow::Owned(value) => filter.resolve_branches(value).map(|branches| {
+                branches
+                    .into_iter()
+                    .map(|(path, value)| (path, Cow::Owned(value.into_owned())))
+                    .collect()
+            }),
+        };
+        let empty_container = ctx
+            .options
+            .filter(|options| options.missing_containers_empty)
+            .and_then(|_| filter.operator.empty_container());
+        let missing = (filter.operator.missing_result(), empty_container);
+        let (branches, exhausted) = match (resolved, missing) {
+            (Ok(branches), _) => (branches, quantifier == Quantifier::All),
+            (Err(FilterError::PathNotFound(_)), (Some(result), _)) => (Vec::new(), result),
+            (Err(FilterError::PathNotFound(_)), (None, Some(empty))) => (
+                vec![(filter.path.to_string(), Cow::Owned(empty))],
+                quantifier == Quantifier::All,
+            ),
+            (Err(error), _) => {
+                ctx.observe(filter, Err(&error));
+                if !ctx.recovers(&error) {
+                    return Err(ctx.fail(filter, error));
+                }
+                (Vec::new(), false)
+            }
+        };
+
+        Ok(Frame {
+            filter,
+            value,
+            quantifier,
+            branches: branches.into_iter(),
+            exhausted,
+            logical: None,
+        })
+    }
+
+    /// The children of `And`, `Or` and `Not`, which are evaluated iteratively.
+    fn logical_children(&self) -> Option<&'a [Filter]> {
+        match &self.filter.operator {
+            Operator::And(filters) | Operator::Or(filters) if !filters.is_empty() => Some(filters),
+            Operator::Not(inner) => Some(std::slice::from_ref(inner)),
+            _ => None,
+        }
+    }
+
+    fn run(mut stack: Vec<Frame<'a>>, ctx: &Context) -> Result<bool, FilterError> {
+        // The result of the frame popped last, for the logical operator below it
+        let mut finished = None;
+
+        while let Some(frame) = stack.last_mut() {
+            let filter = frame.filter;
+
+            let matched = if let Some(result) = finished.take() {
+                let children = frame.logical_children().unwrap_or_default();
+                let logical = frame
+                    .logical
+                    .as_mut()
+                    .expect("only logical operators push frames");
+                logical.matched = match filter.operator {
+                    Operator::And(_) => logical.matched && result,
+                    Operator::Or(_) => logical.matched || result,
+                    _ => !result,
+                };
+                logical.next += 1;
+
+                // Once decided, the remaining children of `And` / `Or` are skipped
+                let decided = match filter.operator {
+                    Operator::And(_) => !logical.matched,
+                    Operator::Or(_) => logical.matched,
+                    _ => false,
+                };
+                if let Some(child) = children.get(logical.next).filter(|_| !decided) {
+                    let child = Frame::start(child, logical.target.clone(), Quantifier::Any, ctx)?;
+                    stack.push(child);
+                    continue;
+                }
+
+                let matched = logical.matched;
+                frame.logical = None;
+                if ctx.tracks_parents() {
+                    ctx.parents.borrow_mut().pop();
+                }
+                matched
+            } else {
+                let Some((_, target)) = frame.branches.next() else {
+                    finished = Some(frame.exhausted);
+                    stack.pop();
+                    continue;
+                };
+
+                let coercion = ctx
+                    .options
+                    .filter(|_| ctx.coerces())
+                    .and_then(|options| options.coercions.get(&ctx.full_path(filter)));
+                let target = match coercion {
+                    Some(kind) => Cow::Owned(kind.coerce(&target)),
+                    None => target,
+                };
+
+                if let Some(children) = frame.logical_children() {
+                    if ctx.tracks_parents() {
+                        ctx.parents.borrow_mut().push(filter.path.clone());
+                    }
+                    let child = Frame::start(&children[0], target.clone(), Quantifier::Any, ctx)?;
+                    frame.logical = Some(Logical {
+                        target,
+                        next: 0,
+                        matched: matches!(filter.operator, Operator::And(_)),
+                    });
+                    stack.push(child);
+                    continue;
+                }
+
+                if filter.operator.subfilters().is_empty() {
+                    ctx.observe(filter, Ok(&target));
+                    filter.operator.evaluate(&target, &frame.value, ctx)
+                } else if ctx.tracks_parents() {
+                    ctx.parents.borrow_mut().push(filter.path.clone());
+                    let matched = filter.operator.evaluate(&target, &frame.value, ctx);
+                    ctx.parents.borrow_mut().pop();
+                    matched
+                } else {
+                    filter.operator.evaluate(&target, &frame.value, ctx)
+                }
+                .or_else(|error| {
+                    if ctx.recovers(&error) {
+                        Ok(false)
+                    } else {
+                        Err(ctx.fail(filter, error))
+                    }
+                })?
+            };
+
+            match frame.quantifier {
+                Quantifier::Any if matched => finished = Some(true),
+                Quantifier::All if !matched => finished = Some(false),
+                _ => continue,
+            }
+            stack.pop();
+        }
+
+        Ok(finished.expect("the outermost frame always finishes"))
+    }
+}
+
+/// A value selected by a path along with its concrete path. Values computed by a
+/// `#` suffix (e.g. `obj#keys`) are owned, everything else borrows the document.
+type Branch<'a> = (String, Cow<'a, Value>);
+
+fn resolve_branches<'a>(path: &str, value: &'a Value) -> Result<Vec<Branch<'a>>, FilterError> {
+    resolve_parsed(&parse_path(path)?, value)
+}
+
+/// [`resolve_branches`] for a path parsed ahead of time.
+fn resolve_parsed<'a>(path: &ParsedPath, value: &'a Value) -> Result<Vec<Branch<'a>>, FilterError> {
+    let branches = resolve_references(&path.segments, value)?;
+
+    let Some(computed) = &path.computed else {
+        return Ok(branches
+            .into_iter()
+            .map(|(path, value)| (path, Cow::Borrowed(value)))
+            .collect());
+    };
+
+    branches
+        .into_iter()
+        .map(|(path, value)| {
+            let computed_value = compute_path_value(computed, value)?;
+            Ok((format!("{}#{}", path, computed), Cow::Owned(computed_value)))
+        })
+        .collect()
+}
+
+/// Evaluates a `#name` path suffix against the value resolved before it:
+/// `#keys` (object keys), `#length` (characters of a string or elements of an
+/// array) or `#sum` (sum of a numeric array).
+fn compute_path_value(name: &str, value: &Value) -> Result<Value, FilterError> {
+    match name {
+        "length" => match value {
+            Value::String(str) => Ok(Value::from(str.chars().count())),
+            Value::Array(arr) => Ok(Value::from(arr.len())),
+            _ => Err(FilterError::TypeMismatch {
+                expected: "string or array".to_string(),
+                got: format!("{:?}", value),
+            }),
+        },
+        "sum" => {
+            let mut sum = 0.0;
+            for element in as_array(value)? {
+                sum += number(element)?.to_f64();
+            }
+            Ok(Value::from(sum))
+        }
+        "keys" => match value {
+            Value::Object(obj) => Ok(Value::Array(
+                obj.keys().map(|key| Value::String(key.clone())).collect(),
+            )),
+            _ => Err(FilterError::TypeMismatch {
+                expected: "object".to_string(),
+                got: format!("{:?}", value),
+            }),
+        },
+        _ => Err(FilterError::InvalidPath(format!("#{}", name))),
+    }
+}
+
+fn resolve_references<'a>(
+    segments: &[PathSegment],
+    value: &'a Value,
+) -> Result<Vec<(String, &'a Value)>, FilterError> {
+    if segments.is_empty() {
+        return Ok(vec![(".".to_string(), value)]);
+    }
+
+    let mut branches = vec![(String::new(), value)];
+
+    for segment in segments {
+        let mut next = Vec::with_capacity(branches.len());
+
+        for (prefix, current) in branches {
+            match segment {
+                PathSegment::Key(key) => {
+                    let child = current
+                        .get(key)
+                        .ok_or_else(|| FilterError::PathNotFound(key.to_string()))?;
+                    next.push((join_path(&prefix, &quote_key(key)), child));
+                }
+                PathSegment::Wildcard => match current {
+                    Value::Array(arr) => next.extend(
+                        arr.iter()
+                            .enumerate()
+                            .map(|(index, element)| (format!("{}[{}]", prefix, index), element)),
+                    ),
+                    Value::Object(obj) => next.extend(
+                        obj.iter()
+                            .map(|(key, element)| (join_path(&prefix, &quote_key(key)), element)),
+                    ),
+                    _ => {
+                        return Err(FilterError::TypeMismatch {
+                            expected: "array or object".to_string(),
+                            got: format!("{:?}", current),
+                        })
+                    }
+                },
+                PathSegment::Index(index) => {
+                    let element = as_array(current)?
+                        .get(*index)
+                        .ok_or_else(|| FilterError::InvalidArrayIndex(index.to_string()))?;
+                    next.push((format!("{}[{}]", prefix, index), element));
+                }
+                PathSegment::IndexFromEnd(index) => {
+                    let arr = as_array(current)?;
+                    let position = arr
+                        .len()
+                        .checked_sub(*index)
+                        .ok_or_else(|| FilterError::InvalidArrayIndex(format!("-{}", index)))?;
+                    next.push((format!("{}[{}]", prefix, position), &arr[position]));
+                }
+                PathSegment::AllElements => next.extend(
+                    as_array(current)?
+                        .iter()
+                        .enumerate()
+                        .map(|(index, element)| (format!("{}[{}]", prefix, index), element)),
+                ),
+                PathSegment::Where {
+                    key,
+                    op,
+                    value,
+                    all,
+                } => {
+                    let mut selected =
+                        as_array(current)?
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, element)| {
+                                element
+                                    .get(key)
+                                    .is_some_and(|field| where_matches(field, *op, value))
+                            });
+
+                    if *all {
+                        next.extend(
+                            selected.map(|(index, element)| {
+                                (format!("{}[{}]", prefix, index), element)
+                            }),
+                        );
+                    } else {
+                        let (index, element) = selected
+                            .next()
+                            .ok_or_else(|| FilterError::PathNotFound(segment.to_string()))?;
+                        next.push((format!("{}[{}]", prefix, index), element));
+                    }
+                }
+            }
+        }
+
+        branches = next;
+    }
+
+    Ok(branches)
+}
+
+/// Whether an element's `field` passes a `[key<op>value]` selector.
+fn where_matches(field: &Value, op: CompareOp, value: &Value) -> bool {
+    match (op, field, value) {
+        (CompareOp::Eq, field, value) => values_equal(field, value),
+        (CompareOp::Ne, field, value) => !values_equal(field, value),
+        (op, Value::Number(_), Value::Number(_)) => as_number(field)
+            .zip(as_number(value))
+            .and_then(|(a, b)| a.partial_cmp_number(b))
+            .is_some_and(|ordering| op.compare(ordering, Ordering::Equal)),
+        (op, Value::String(a), Value::String(b)) => op.compare(a, b),
+        _ => false,
+    }
+}
+
+fn flatten_into(arr: &[Value], depth: usize, flattened: &mut Vec<Value>) {
+    for element in arr {
+        match element {
+            Value::Array(nested) if depth > 0 => flatten_into(nested, depth - 1, flattened),
+            element => flattened.push(element.clone()),
+        }
+    }
+}
+
+fn as_array(value: &Value) -> Result<&Vec<Value>, FilterError> {
+    match value {
+        Value::Array(arr) => Ok(arr),
+        _ => Err(FilterError::TypeMismatch {
+            expected: "array".to_string(),
+            got: format!("{:?}", value),
+        }),
+    }
+}
+
+/// A JSON number normalized so that equal numbers have a single
+/// representation: integral values (including integral floats such as `5.0`)
+/// become `Int`/`UInt`, and `-0.0` becomes `Int(0)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NumRepr {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+}
+
+impl NumRepr {
+    fn to_f64(self) -> f64 {
+        match self {
+            NumRepr::Int(i) => i as f64,
+            NumRepr::UInt(u) => u as f64,
+            NumRepr::Float(f) => f,
+        }
+    }
+
+    /// Compares exactly, so large integers that don't fit in an `f64` (e.g.
+    /// `2^53 + 1`) still compare correctly against nearby floats.
+    fn partial_cmp_f64(self, rhs: f64) -> Option<Ordering> {
+        let int = match self {
+            NumRepr::Int(i) => i128::from(i),
+            NumRepr::UInt(u) => i128::from(u),
+            NumRepr::Float(f) => return f.partial_cmp(&rhs),
+        };
+
+        // Every i64 and u64 lies in [-2^63, 2^64), within which integral floats
+        // convert to i128 exactly
+        if rhs.is_nan() {
+            None
+        } else if rhs >= 18_446_744_073_709_551_616.0 {
+            Some(Ordering::Less)
+        } else if rhs < -9_223_372_036_854_775_808.0 {
+            Some(Ordering::Greater)
+        } else {
+            let floor = rhs.floor();
+            match int.cmp(&(floor as i128)) {
+                Ordering::Equal if rhs > floor => Some(Ordering::Less),
+                ordering => Some(ordering),
+            }
+        }
+    }
+
+    /// Compares exactly, like [`NumRepr::partial_cmp_f64`].
+    fn partial_cmp_number(self, rhs: NumRepr) -> Option<Ordering> {
+        match (self, rhs) {
+            (lhs, NumRepr::Float(rhs)) => lhs.partial_cmp_f64(rhs),
+            (NumRepr::Float(lhs), rhs) => rhs.partial_cmp_f64(lhs).map(Ordering::reverse),
+            (lhs, rhs) => Some(lhs.to_i128().cmp(&rhs.to_i128())),
+        }
+    }
+
+    /// The value of an integer, `None` for a float.
+    fn to_i128(self) -> Option<i128> {
+        match self {
+            NumRepr::Int(i) => Some(i128::from(i)),
+            NumRepr::UInt(u) => Some(i128::from(u)),
+            NumRepr::Float(_) => None,
+        }
+    }
+}
+
+fn as_number(v: &Value) -> Option<NumRepr> {
+    let Value::Number(num) = v else {
+        return None;
+    };
+
+    if let Some(i) = num.as_i64() {
+        Some(NumRepr::Int(i))
+    } else if let Some(u) = num.as_u64() {
+        Some(NumRepr::UInt(u))
+    } else {
+        let f = num.as_f64()?;
+        // Integral floats in [-2^63, 2^64) convert exactly, so folding them never
+        // changes the value and `2^60` equals `2^60.0` as it compares equal
+        if f.fract() != 0.0 {
+            Some(NumRepr::Float(f))
+        } else if (-9_223_372_036_854_775_808.0..9_223_372_036_854_775_808.0).contains(&f) {
+            Some(NumRepr::Int(f as i64))
+        } else if (0.0..18_446_744_073_709_551_616.0).contains(&f) {
+            Some(NumRepr::UInt(f as u64))
+        } else {
+            Some(NumRepr::Float(f))
+        }
+    }
+}
+
+/// [`as_number`] for operators that require a number. JSON numbers that
+/// can't be read as one (only possible with serde_json's `arbitrary_precision`
+/// feature, enabled by this crate's `arbitrary-precision` feature) are reported
+/// as `InvalidNumber` rather than a type mismatch.
+fn number(value: &Value) -> Result<NumRepr, FilterError> {
+    match (as_number(value), value) {
+        (Some(number), _) => Ok(number),
+        (None, Value::Number(number)) => Err(FilterError::InvalidNumber(number.to_string())),
+        (None, _) => Err(FilterError::TypeMismatch {
+            expected: "number".to_string(),
+            got: format!("{:?}", value),
+        }),
+    }
+}
+
+/// `1 - levenshtein(a, b) / max(len(a), len(b))` over chars, `1.0` for two
+/// empty strings.
+fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+/// Matches `s` against a glob where `*` stands for any run of characters and
+/// `?` for exactly one.
+fn glob_matches(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    let (mut p, mut i) = (0, 0);
+    // Position of the last `*` and the character it's currently matched up to
+    let mut backtrack = None;
+
+    while i < s.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, i));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    i = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// The value as compact JSON, cut off after 64 characters.
+fn preview(value: &Value) -> String {
+    let json = value.to_string();
+    match json.char_indices().nth(64) {
+        Some((end, _)) => format!("{}...", &json[..end]),
+        None => json,
+    }
+}
+
+/// Great-circle distance between two points given in degrees, on a sphere with
+/// the Earth's mean radius.
+#[cfg(feature = "geo")]
+fn haversine_km(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lng = (lng2 - lng1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lng / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+fn luhn_valid(s: &str) -> bool {
+    if s.len() < 2 || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let sum: u32 = s
+        .bytes()
+        .rev()
+        .map(|b| u32::from(b - b'0'))
+        .enumerate()
+        .map(|(i, digit)| match (i % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn ulps_equal(a: f64, b: f64, max_ulps: u32) -> bool {
+    if a.is_nan() || b.is_nan() {
+        return false;
+    }
+    // Also covers 0.0 == -0.0, whose bit patterns are far apart
+    if a == b {
+        return true;
+    }
+    if a.is_sign_negative() != b.is_sign_negative() {
+        return false;
+    }
+    (a.to_bits() as i64 - b.to_bits() as i64).unsigned_abs() <= max_ulps as u64
+}
+
+/// Equality used by `Equals` / `NotEqual`: structural, except that numbers
+/// compare by value, so `5` equals `5.0`.
+fn values_equal(a: &Value, b: &Value) -> bool {
+    deep_equals(a, b, false)
+}
+
+fn deep_equals(a: &Value, b: &Value, ignore_array_order: bool) -> bool {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter().all(|(key, a)| {
+                    b.get(key)
+                        .is_some_and(|b| deep_equals(a, b, ignore_array_order))
+                })
+        }
+        (Value::Array(a), Value::Array(b)) if ignore_array_order => {
+            if a.len() != b.len() {
+                return false;
+            }
+            let mut used = vec![false; b.len()];
+            a.iter().all(|a| {
+                let found = b
+                    .iter()
+                    .enumerate()
+                    .position(|(i, b)| !used[i] && deep_equals(a, b, true));
+                if let Some(i) = found {
+                    used[i] = true;
+                }
+                found.is_some()
+            })
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|(a, b)| deep_equals(a, b, ignore_array_order))
+        }
+        (Value::Number(_), Value::Number(_)) => as_number(a) == as_number(b),
+        (a, b) => a == b,
+    }
+}
+
+/// Joins a nested filter path onto the path of its parent filter.
+fn nested_path(parent: &str, child: &str) -> String {
+    match (parent, child) {
+        (".", child) => child.to_string(),
+        (parent, ".") => parent.to_string(),
+        (parent, child) if child.starts_with('[') => format!("{}{}", parent, child),
+        (parent, child) => format!("{}.{}", parent, child),
+    }
+}
+
+fn join_path(prefix: &str, segment: &str) -> String {
+    if prefix.is_empty() || segment.starts_with('[') {
+        format!("{}{}", prefix, segment)
+    } else {
+        format!("{}.{}", prefix, segment)
+    }
+}
+
+/// Parses a human readable byte size such as `"10MB"`, `"1.5 GiB"` or `"512"`
+/// into a number of bytes. Decimal units (`KB`, `MB`, ...) are powers of 1000,
+/// binary units (`KiB`, `MiB`, ...) are powers of 1024.
+#[cfg(feature = "byte-size")]
+fn parse_byte_size(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "kb" | "k" => 1e3,
+        "mb" | "m" => 1e6,
+        "gb" | "g" => 1e9,
+        "tb" | "t" => 1e12,
+        "kib" => 1024.0,
+        "mib" => 1024.0 * 1024.0,
+        "gib" => 1024.0 * 1024.0 * 1024.0,
+        "tib" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+
+    Some(number * multiplier)
+}
+
+/// Decomposes `s` (NFD) and drops the combining marks, e.g. `é` becomes `e`.
+#[cfg(feature = "diacritics")]
+fn strip_diacritics(s: &str) -> String {
+    use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+    s.nfd().filter(|&c| !is_combining_mark(c)).collect()
+}
+
+/// Applies `inner` to capture `group` of the first match of `regex` in `s`,
+/// `false` when either doesn't match.
+#[cfg(feature = "regex")]
+fn capture_matches(
+    regex: &regex::Regex,
+    group: &CaptureGroup,
+    inner: &Operator,
+    s: &str,
+    scope: &Value,
+    ctx: &Context,
+) -> Result<bool, FilterError> {
+    group.validate(regex)?;
+    match regex.captures(s).and_then(|captures| group.get(&captures)) {
+        Some(capture) => inner.evaluate(&Value::String(capture.as_str().to_string()), scope, ctx),
+        None => Ok(false),
+    }
+}
+
+/// A primary-strength collator for `locale`, which compares base letters only.
+#[cfg(feature = "collation")]
+fn collator(locale: &str) -> Result<icu_collator::Collator, FilterError> {
+    let parsed: icu_locid::Locale = locale
+        .parse()
+        .map_err(|_| FilterError::InvalidLocale(locale.to_string()))?;
+
+    // Collation data falls back to the root for any language, so whether the
+    // language is known is looked up in the likely subtags instead, which
+    // have an entry for every language in CLDR and no fallback
+    let mut language = icu_locid::LanguageIdentifier::from(parsed.id.language);
+    let expander = icu_locid_transform::LocaleExpander::new_extended();
+    if expander.maximize(&mut language) == icu_locid_transform::TransformResult::Unmodified {
+        return Err(FilterError::InvalidLocale(locale.to_string()));
+    }
+
+    let mut options = icu_collator::CollatorOptions::new();
+    options.strength = Some(icu_collator::Strength::Primary);
+    icu_collator::Collator::try_new(&(&parsed).into(), options)
+        .map_err(|e| FilterError::InvalidLocale(format!("{}: {}", locale, e)))
+}
+
+/// Parses a `WithinDistance` coordinate path, which must select a single value.
+#[cfg(feature = "geo")]
+fn coordinate_path(path: &str) -> Result<ParsedPath, FilterError> {
+    let parsed = parse_path(path)?;
+    let fans_out = parsed.segments.iter().any(|segment| {
+        matches!(
+            segment,
+            PathSegment::Wildcard | PathSegment::AllElements | PathSegment::Where { all: true, .. }
+        )
+    });
+    if fans_out {
+        return Err(FilterError::InvalidOperand(format!(
+            "coordinate path {} selects several values",
+            path
+        )));
+    }
+    Ok(parsed)
+}
+
+#[cfg(feature = "datetime")]
+fn parse_timestamp(value: &Value) -> Result<chrono::DateTime<chrono::FixedOffset>, FilterError> {
+    match value {
+        Value::String(str) => chrono::DateTime::parse_from_rfc3339(str)
+            .map_err(|_| FilterError::InvalidTimestamp(str.to_string())),
+        _ => Err(FilterError::TypeMismatch {
+            expected: "string".to_string(),
+            got: format!("{:?}", value),
+        }),
+    }
+}
+
+/// American Soundex code of the ASCII letters in `s`, or an empty string if
+/// there are none.
+#[cfg(feature = "phonetic")]
+fn soundex(s: &str) -> String {
+    fn digit(c: char) -> Option<char> {
+        match c {
+            'B' | 'F' | 'P' | 'V' => Some('1'),
+            'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => Some('2'),
+            'D' | 'T' => Some('3'),
+            'L' => Some('4'),
+            'M' | 'N' => Some('5'),
+            'R' => Some('6'),
+            _ => None,
+        }
+    }
+
+    let mut letters = s
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_uppercase());
+    let Some(first) = letters.next() else {
+        return String::new();
+    };
+
+    let mut code = first.to_string();
+    let mut last = digit(first);
+    for c in letters {
+        let d = digit(c);
+        if let Some(digit) = d.filter(|_| d != last) {
+            code.push(digit);
+            if code.len() == 4 {
+                break;
+            }
+        }
+        // `H` and `W` don't separate letters with the same code, vowels do
+        if c != 'H' && c != 'W' {
+            last = d;
+        }
+    }
+
+    format!("{:0<4}", code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_numeric_operators() {
+        let value = json!({ "age": 25 });
+
+        let filter = Filter::new("age", Operator::GreaterThan(20.0));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("age", Operator::LessThan(30.0));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("age", Operator::GreaterOrEqual(25.0));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("age", Operator::LessOrEqual(25.0));
+        assert!(filter.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_large_integer_comparison() {
+        let value = json!({ "id": 9007199254740993u64, "max": u64::MAX, "min": i64::MIN });
+        let check = |path: &str, operator: Operator| Filter::new(path, operator).check(&value);
+
+        // 2^53 + 1 isn't representable as an f64, which would round it down
+        assert!(check("id", Operator::GreaterThan(9007199254740992.0)).unwrap());
+        assert!(!check("id", Operator::LessOrEqual(9007199254740992.0)).unwrap());
+        assert!(check("id", Operator::LessThan(9007199254740994.0)).unwrap());
+        assert!(check("id", Operator::GreaterThan(9007199254740992.5)).unwrap());
+
+        assert!(check("max", Operator::LessThan(18446744073709551616.0)).unwrap());
+        assert!(check("max", Operator::GreaterThan(18446744073709549568.0)).unwrap());
+        assert!(check("max", Operator::LessThan(f64::INFINITY)).unwrap());
+        assert!(check("min", Operator::GreaterOrEqual(-9223372036854775808.0)).unwrap());
+        assert!(!check("min", Operator::GreaterThan(-9223372036854775808.0)).unwrap());
+        assert!(check("min", Operator::LessThan(-9223372036854774784.0)).unwrap());
+        assert!(!check("id", Operator::GreaterThan(f64::NAN)).unwrap());
+    }
+
+    #[test]
+    fn test_numeric_normalization() {
+        assert_eq!(as_number(&json!(-0.0)), as_number(&json!(0)));
+        assert_eq!(as_number(&json!(5.0)), as_number(&json!(5)));
+        assert_eq!(as_number(&json!(5.5)), Some(NumRepr::Float(5.5)));
+        assert_eq!(as_number(&json!("5")), None);
+
+        let value = json!({ "zero": -0.0, "id": 9007199254740992u64 });
+
+        let filter = Filter::new("zero", Operator::GreaterOrEqual(0.0));
+        assert!(filter.check(&value).unwrap());
+        let filter = Filter::new("zero", Operator::LessThan(0.0));
+        assert!(!filter.check(&value).unwrap());
+        let filter = Filter::new("zero", Operator::GreaterThan(-0.0));
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new("id", Operator::GreaterOrEqual(9007199254740992.0));
+        assert!(filter.check(&value).unwrap());
+        let filter = Filter::new("id", Operator::LessOrEqual(9007199254740992.0));
+        assert!(filter.check(&value).unwrap());
+        let filter = Filter::new("id", Operator::GreaterThan(9007199254740992.0));
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new("id", Operator::GreaterThan(f64::NAN));
+        assert!(!filter.check(&value).unwrap());
+    }
+
+    #[cfg(feature = "arbitrary-precision")]
+    #[test]
+    fn test_invalid_number() {
+        // Out of `f64` range, kept as written under arbitrary precision
+        let value: Value = serde_json::from_str(r#"{ "huge": 1e400, "ok": 1 }"#).unwrap();
+
+        for operator in [
+            Operator::GreaterThan(1.0),
+            Operator::Between {
+                min: 0.0,
+                max: 1.0,
+                inclusive: true,
+            },
+            Operator::Sign(Sign::Positive),
+            Operator::MultipleOfPath("ok".to_string()),
+        ] {
+            assert!(matches!(
+                Filter::new("huge", operator).check(&value),
+                Err(FilterError::InvalidNumber(number)) if number == "1e400"
+            ));
+        }
+        let filter = Filter::new("ok", Operator::MultipleOfPath("huge".to_string()));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::InvalidNumber(..))
+        ));
+    }
+
+    #[test]
+    fn test_numeric_equality() {
+        let value = json!({ "count": 5.0, "nested": { "ids": [1.0, 2] }, "name": "5" });
+
+        let filter = Filter::new("count", Operator::Equals(json!(5)));
+        assert!(filter.check(&value).unwrap());
+        let filter = Filter::new("count", Operator::NotEqual(json!(5)));
+        assert!(!filter.check(&value).unwrap());
+        let filter = Filter::new("count", Operator::NotEqual(json!(6)));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("nested", Operator::Equals(json!({ "ids": [1, 2.0] })));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("name", Operator::Equals(json!(5)));
+        assert!(!filter.check(&value).unwrap());
+        let filter = Filter::new("name", Operator::NotEqual(json!(5)));
+        assert!(filter.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_numeric_equality_beyond_f64_precision() {
+        // 2^60 is exactly representable as a float, so both sides are the same
+        // number for equality just as they are for ordering
+        let value =
+            json!({ "int": 1u64 << 60, "float": 1_152_921_504_606_846_976.0, "big": 1u64 << 63 });
+
+        for (path, operand) in [
+            ("int", json!(1_152_921_504_606_846_976.0)),
+            ("float", json!(1u64 << 60)),
+        ] {
+            assert!(Filter::new(path, Operator::Equals(operand.clone()))
+                .check(&value)
+                .unwrap());
+            let filter = Filter::new(path, Operator::In(vec![json!(1), operand]));
+            assert!(filter.check(&value).unwrap());
+            assert!(filter.compile().check(&value).unwrap());
+            let filter = Filter::new(path, Operator::GreaterOrEqual(1_152_921_504_606_846_976.0));
+            assert!(filter.check(&value).unwrap());
+            let filter = Filter::new(path, Operator::LessOrEqual(1_152_921_504_606_846_976.0));
+            assert!(filter.check(&value).unwrap());
+        }
+
+        let filter = Filter::new("big", Operator::Equals(json!(9_223_372_036_854_775_808.0)));
+        assert!(filter.check(&value).unwrap());
+        let filter = Filter::new("int", Operator::Equals(json!((1u64 << 60) + 1)));
+        assert!(!filter.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_ulp_equals() {
+        let one = 1.0_f64;
+        let next = f64::from_bits(one.to_bits() + 1);
+        let value = json!({ "x": next, "zero": -0.0, "s": "1.0" });
+
+        let filter = Filter::new(
+            "x",
+            Operator::UlpEquals {
+                target: one,
+                max_ulps: 1,
+            },
+        );
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new(
+            "x",
+            Operator::UlpEquals {
+                target: one,
+                max_ulps: 0,
+            },
+        );
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new(
+            "x",
+            Operator::UlpEquals {
+                target: 1.001,
+                max_ulps: 4,
+            },
+        );
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new(
+            "zero",
+            Operator::UlpEquals {
+                target: 0.0,
+                max_ulps: 0,
+            },
+        );
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new(
+            "zero",
+            Operator::UlpEquals {
+                target: f64::MIN_POSITIVE,
+                max_ulps: 4,
+            },
+        );
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new(
+            "s",
+            Operator::UlpEquals {
+                target: one,
+                max_ulps: 1,
+            },
+        );
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_multiple_of_path() {
+        let value = json!({
+            "total": 45, "unit_price": 15, "odd": 50,
+            "weight": 0.3, "step": 0.1,
+            "zero": 0, "label": "x",
+        });
+        let check = |path: &str, divisor: &str| {
+            Filter::new(path, Operator::MultipleOfPath(divisor.to_string())).check(&value)
+        };
+
+        assert!(check("total", "unit_price").unwrap());
+        assert!(!check("odd", "unit_price").unwrap());
+        assert!(check("weight", "step").unwrap());
+        assert!(check("zero", "unit_price").unwrap());
+        assert!(!check("step", "weight").unwrap());
+
+        assert!(matches!(
+            check("total", "zero"),
+            Err(FilterError::InvalidOperand(message)) if message == "zero divisor at zero"
+        ));
+        assert!(matches!(
+            check("label", "unit_price"),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            check("total", "label"),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_aggregate_equals_path() {
+        let invoice = |total: Value| {
+            json!({
+                "invoice": {
+                    "lineItems": [{ "amount": 10.1 }, { "amount": 0.2 }, { "amount": 5 }],
+                    "total": total
+                }
+            })
+        };
+        let aggregate = |reducer, field: &str| {
+            Filter::new(
+                "invoice",
+                Operator::AggregateEqualsPath {
+                    array_path: "lineItems".to_string(),
+                    reducer,
+                    field: field.to_string(),
+                    other_path: "total".to_string(),
+                },
+            )
+        };
+        let sum = aggregate(Reducer::Sum, "amount");
+
+        assert!(sum.check(&invoice(json!(15.3))).unwrap());
+        assert!(!sum.check(&invoice(json!(15.4))).unwrap());
+        assert!(aggregate(Reducer::Max, "amount")
+            .check(&invoice(json!(10.1)))
+            .unwrap());
+        assert!(aggregate(Reducer::Min, "amount")
+            .check(&invoice(json!(0.2)))
+            .unwrap());
+        assert!(aggregate(Reducer::Average, "amount")
+            .check(&invoice(json!(5.1)))
+            .unwrap());
+        assert!(aggregate(Reducer::Count, "amount")
+            .check(&invoice(json!(3)))
+            .unwrap());
+
+        let empty = json!({ "invoice": { "lineItems": [], "total": 0 } });
+        assert!(sum.check(&empty).unwrap());
+        assert!(!aggregate(Reducer::Min, "amount").check(&empty).unwrap());
+
+        let numbers = json!({ "invoice": { "lineItems": [1, 2, 3], "total": 6 } });
+        assert!(aggregate(Reducer::Sum, ".").check(&numbers).unwrap());
+
+        let strings = json!({ "invoice": { "lineItems": [{ "amount": "10" }], "total": 10 } });
+        assert!(matches!(
+            sum.check(&strings),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            sum.check(&json!({ "invoice": { "lineItems": {}, "total": 0 } })),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            sum.check(&json!({ "invoice": { "lineItems": [] } })),
+            Err(FilterError::PathNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_rounds_to() {
+        let rounds_to = |price: Value, value: f64, decimals: u32| {
+            Filter::new("price", Operator::RoundsTo { value, decimals })
+                .check(&json!({ "price": price }))
+        };
+
+        assert!(rounds_to(json!(9.996), 10.0, 2).unwrap());
+        assert!(!rounds_to(json!(9.996), 10.0, 3).unwrap());
+        assert!(rounds_to(json!(9.996), 9.996, 3).unwrap());
+        assert!(rounds_to(json!(-2.5), -3.0, 0).unwrap());
+        assert!(rounds_to(json!(0.1 + 0.2), 0.3, 2).unwrap());
+        assert!(rounds_to(json!(42), 42.0, 400).unwrap());
+        assert!(matches!(
+            rounds_to(json!("9.996"), 10.0, 2),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_between() {
+        let value = json!({ "age": 25, "name": "John" });
+        let between = |path: &str, min: f64, max: f64, inclusive: bool| {
+            Filter::new(
+                path,
+                Operator::Between {
+                    min,
+                    max,
+                    inclusive,
+                },
+            )
+            .check(&value)
+        };
+
+        assert!(between("age", 18.0, 30.0, true).unwrap());
+        assert!(between("age", 25.0, 30.0, true).unwrap());
+        assert!(!between("age", 25.0, 30.0, false).unwrap());
+        assert!(!between("age", 30.0, 40.0, true).unwrap());
+
+        // An empty range never matches
+        assert!(!between("age", 30.0, 18.0, true).unwrap());
+        assert!(!between("age", 25.0, 25.0, false).unwrap());
+
+        assert!(matches!(
+            between("name", 18.0, 30.0, true),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_in_ranges() {
+        let ranges = vec![(0.0, 12.0), (65.0, 120.0)];
+        let check = |age: Value, ranges: Vec<(f64, f64)>| {
+            Filter::new("age", Operator::InRanges(ranges)).check(&json!({ "age": age }))
+        };
+
+        assert!(check(json!(0), ranges.clone()).unwrap());
+        assert!(check(json!(7.5), ranges.clone()).unwrap());
+        assert!(check(json!(65), ranges.clone()).unwrap());
+        assert!(check(json!(120), ranges.clone()).unwrap());
+        assert!(!check(json!(13), ranges.clone()).unwrap());
+        assert!(!check(json!(121), ranges.clone()).unwrap());
+
+        assert!(!check(json!(5), vec![]).unwrap());
+        // Inverted ranges are skipped
+        assert!(!check(json!(5), vec![(10.0, 0.0)]).unwrap());
+        assert!(check(json!(5), vec![(10.0, 0.0), (5.0, 5.0)]).unwrap());
+
+        assert!(matches!(
+            check(json!("5"), ranges),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_range() {
+        let value = json!({ "low": 1, "mid": 5, "high": 10, "s": "5" });
+        let range = |path: &str, min: Option<f64>, max: Option<f64>, inclusive: bool| {
+            Filter::new(
+                path,
+                Operator::Range {
+                    min,
+                    max,
+                    min_inclusive: inclusive,
+                    max_inclusive: inclusive,
+                },
+            )
+            .check(&value)
+        };
+
+        // Both bounds
+        assert!(range("mid", Some(1.0), Some(10.0), false).unwrap());
+        assert!(range("low", Some(1.0), Some(10.0), true).unwrap());
+        assert!(!range("low", Some(1.0), Some(10.0), false).unwrap());
+        assert!(range("high", Some(1.0), Some(10.0), true).unwrap());
+        assert!(!range("high", Some(1.0), Some(10.0), false).unwrap());
+
+        // Only a lower bound
+        assert!(range("high", Some(5.0), None, false).unwrap());
+        assert!(range("mid", Some(5.0), None, true).unwrap());
+        assert!(!range("mid", Some(5.0), None, false).unwrap());
+
+        // Only an upper bound
+        assert!(range("low", None, Some(5.0), false).unwrap());
+        assert!(range("mid", None, Some(5.0), true).unwrap());
+        assert!(!range("mid", None, Some(5.0), false).unwrap());
+
+        // No bounds
+        assert!(range("mid", None, None, false).unwrap());
+
+        assert!(matches!(
+            range("s", None, None, true),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sign() {
+        let value = json!({ "pos": 3, "neg": -2.5, "zero": 0, "negzero": -0.0, "s": "1" });
+        let check = |path: &str, sign: Sign| Filter::new(path, Operator::Sign(sign)).check(&value);
+
+        for (path, expected) in [
+            ("pos", [true, false, false, true, false]),
+            ("neg", [false, true, false, false, true]),
+            ("zero", [false, false, true, true, true]),
+            ("negzero", [false, false, true, true, true]),
+        ] {
+            let signs = [
+                Sign::Positive,
+                Sign::Negative,
+                Sign::Zero,
+                Sign::NonNegative,
+                Sign::NonPositive,
+            ];
+            for (sign, expected) in signs.into_iter().zip(expected) {
+                assert_eq!(check(path, sign).unwrap(), expected, "{} {:?}", path, sign);
+            }
+        }
+
+        assert!(matches!(
+            check("s", Sign::Positive),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_greater_than_scaled() {
+        let value = json!({ "price_cents": 1999, "discount_cents": 500, "label": "1999" });
+        let over = |path: &str, dollars: f64| {
+            Filter::new(
+                path,
+                Operator::GreaterThanScaled {
+                    value: dollars,
+                    scale: 100.0,
+                },
+            )
+            .check(&value)
+        };
+
+        assert!(over("price_cents", 19.98).unwrap());
+        assert!(!over("price_cents", 19.99).unwrap());
+        assert!(!over("discount_cents", 10.0).unwrap());
+        assert!(matches!(
+            over("label", 10.0),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+
+        // Rejected when evaluated too, not only by `validate`
+        let filter = Filter::new(
+            "price_cents",
+            Operator::GreaterThanScaled {
+                value: 10.0,
+                scale: 0.0,
+            },
+        );
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::InvalidOperand(..))
+        ));
+    }
+
+    #[test]
+    fn test_string_operators() {
+        let value = json!({ "name": "John Doe" });
+
+        let filter = Filter::new("name", Operator::StartsWith("John".to_string()));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("name", Operator::EndsWith("Doe".to_string()));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("name", Operator::Contains("hn D".to_string()));
+        assert!(filter.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_ignore_case_operators() {
+        let value = json!({ "name": "JOHN DOE", "age": 30 });
+        let check = |path: &str, operator: Operator| Filter::new(path, operator).check(&value);
+
+        assert!(check("name", Operator::ContainsIgnoreCase("john".to_string())).unwrap());
+        assert!(!check("name", Operator::Contains("john".to_string())).unwrap());
+        assert!(check("name", Operator::StartsWithIgnoreCase("John".to_string())).unwrap());
+        assert!(check("name", Operator::EndsWithIgnoreCase("doe".to_string())).unwrap());
+        assert!(!check("name", Operator::EndsWithIgnoreCase("john".to_string())).unwrap());
+        assert!(check("name", Operator::EqualsIgnoreCase("John Doe".to_string())).unwrap());
+        assert!(!check("name", Operator::EqualsIgnoreCase("John".to_string())).unwrap());
+
+        for operator in [
+            Operator::EqualsIgnoreCase("30".to_string()),
+            Operator::StartsWithIgnoreCase("3".to_string()),
+            Operator::EndsWithIgnoreCase("0".to_string()),
+            Operator::ContainsIgnoreCase("3".to_string()),
+        ] {
+            assert!(matches!(
+                check("age", operator),
+                Err(FilterError::TypeMismatch { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_equals_trimmed() {
+        let value = json!({ "name": "  John \u{3000}", "age": 25 });
+
+        let filter = Filter::new("name", Operator::EqualsTrimmed("John".to_string()));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("name", Operator::EqualsTrimmed(" John\t".to_string()));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("name", Operator::Equals(json!("John")));
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new("age", Operator::EqualsTrimmed("25".to_string()));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_equals_normalized_whitespace() {
+        let value = json!({ "code": " a   b\n\tc ", "id": 1 });
+
+        let filter = Filter::new(
+            "code",
+            Operator::EqualsNormalizedWhitespace("a b c".to_string()),
+        );
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("code", Operator::Equals(json!("a b c")));
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new(
+            "code",
+            Operator::EqualsNormalizedWhitespace("ab c".to_string()),
+        );
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new("id", Operator::EqualsNormalizedWhitespace("1".to_string()));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_looks_like_number() {
+        let value = json!({ "a": "42", "b": "4.2", "c": "4.2.1", "d": "abc", "e": " -7 ", "f": "NaN", "n": 42 });
+        let check = |path: &str, operator: Operator| Filter::new(path, operator).check(&value);
+
+        for (path, number, integer) in [
+            ("a", true, true),
+            ("b", true, false),
+            ("c", false, false),
+            ("d", false, false),
+            ("e", true, true),
+            ("f", false, false),
+        ] {
+            assert_eq!(
+                check(path, Operator::LooksLikeNumber).unwrap(),
+                number,
+                "{}",
+                path
+            );
+            assert_eq!(
+                check(path, Operator::LooksLikeInteger).unwrap(),
+                integer,
+                "{}",
+                path
+            );
+        }
+
+        assert!(matches!(
+            check("n", Operator::LooksLikeNumber),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_luhn() {
+        let check =
+            |card: Value| Filter::new("card", Operator::Luhn).check(&json!({ "card": card }));
+
+        assert!(check(json!("4111111111111111")).unwrap());
+        assert!(check(json!("79927398713")).unwrap());
+        assert!(!check(json!("4111111111111112")).unwrap());
+        assert!(!check(json!("79927398710")).unwrap());
+
+        assert!(!check(json!("4111 1111 1111 1111")).unwrap());
+        assert!(!check(json!("0")).unwrap());
+        assert!(!check(json!("")).unwrap());
+        assert!(matches!(
+            check(json!(4111111111111111u64)),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_score() {
+        let value = json!({ "name": "Jon Smith", "age": 30 });
+        let fuzzy = Filter::new(
+            "name",
+            Operator::FuzzyMatches {
+                target: "John Smith".to_string(),
+                min_similarity: 0.8,
+            },
+        );
+        let adult = Filter::new("age", Operator::GreaterOrEqual(18.0));
+        let senior = Filter::new("age", Operator::GreaterOrEqual(65.0));
+
+        assert_eq!(adult.score(&value).unwrap(), 1.0);
+        assert_eq!(senior.score(&value).unwrap(), 0.0);
+        assert_eq!(fuzzy.score(&value).unwrap(), 0.9);
+        assert!(fuzzy.check(&value).unwrap());
+
+        let both = Filter::new(".", Operator::And(vec![fuzzy.clone(), adult]));
+        assert_eq!(both.score(&value).unwrap(), 0.9);
+        let either = Filter::new(".", Operator::Or(vec![fuzzy.clone(), senior.clone()]));
+        assert_eq!(either.score(&value).unwrap(), 0.9);
+        let neither = Filter::new(".", Operator::And(vec![fuzzy, senior]));
+        assert_eq!(neither.score(&value).unwrap(), 0.0);
+
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("kitten", "sitting"), 1.0 - 3.0 / 7.0);
+    }
+
+    #[test]
+    fn test_format() {
+        let value = json!({
+            "id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "bad_id": "67e55044-10b1-426f-9247-bb680e5fe0c",
+            "email": "john@example.com",
+            "bad_email": "john@example",
+            "url": "https://example.com/path?q=1",
+            "bad_url": "example.com",
+            "ipv4": "192.168.0.1",
+            "ipv6": "::1",
+            "age": 25
+        });
+        let check =
+            |path: &str, kind: FormatKind| Filter::new(path, Operator::Format(kind)).check(&value);
+
+        assert!(check("id", FormatKind::Uuid).unwrap());
+        assert!(!check("bad_id", FormatKind::Uuid).unwrap());
+        assert!(check("email", FormatKind::Email).unwrap());
+        assert!(!check("bad_email", FormatKind::Email).unwrap());
+        assert!(check("url", FormatKind::Url).unwrap());
+        assert!(!check("bad_url", FormatKind::Url).unwrap());
+        assert!(check("ipv4", FormatKind::Ipv4).unwrap());
+        assert!(!check("ipv6", FormatKind::Ipv4).unwrap());
+        assert!(check("ipv6", FormatKind::Ipv6).unwrap());
+        assert!(matches!(
+            check("age", FormatKind::Uuid),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_array_operators() {
+        let value = json!({ "tags": ["rust", "coding", "json"] });
+
+        let filter = Filter::new("tags", Operator::ArrayContains(json!("rust")));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("tags[1]", Operator::Equals(json!("coding")));
+        assert!(filter.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_array_contains_all_any() {
+        let value = json!({ "tags": ["rust", "json", 1] });
+        let check = |operator: Operator| Filter::new("tags", operator).check(&value);
+
+        assert!(check(Operator::ArrayContainsAll(vec![
+            json!("json"),
+            json!("rust")
+        ]))
+        .unwrap());
+        assert!(!check(Operator::ArrayContainsAll(vec![json!("rust"), json!("go")])).unwrap());
+        assert!(check(Operator::ArrayContainsAll(vec![])).unwrap());
+        assert!(check(Operator::ArrayContainsAny(vec![json!("go"), json!(1)])).unwrap());
+        assert!(!check(Operator::ArrayContainsAny(vec![json!("go"), json!("1")])).unwrap());
+        assert!(!check(Operator::ArrayContainsAny(vec![])).unwrap());
+
+        let filter = Filter::new("tags", Operator::ArrayContainsAll(vec![]));
+        assert!(filter.check(&json!({ "tags": [] })).unwrap());
+        assert!(matches!(
+            filter.check(&json!({ "tags": "rust" })),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_all_any_elements() {
+        let value = json!({
+            "orders": [
+                { "total": 10, "status": "shipped" },
+                { "total": 25, "status": "pending" }
+            ],
+            "scores": [3, 7]
+        });
+        let positive = Filter::new("total", Operator::GreaterThan(0.0));
+        let shipped = Filter::new("status", Operator::Equals(json!("shipped")));
+        let check = |path: &str, operator: Operator| Filter::new(path, operator).check(&value);
+
+        assert!(check("orders", Operator::All(Box::new(positive.clone()))).unwrap());
+        assert!(!check("orders", Operator::All(Box::new(shipped.clone()))).unwrap());
+        assert!(check("orders", Operator::Any(Box::new(shipped.clone()))).unwrap());
+        let above_five = Filter::new(".", Operator::GreaterThan(5.0));
+        assert!(!check("scores", Operator::All(Box::new(above_five.clone()))).unwrap());
+        assert!(check("scores", Operator::Any(Box::new(above_five.clone()))).unwrap());
+
+        let empty = json!({ "orders": [] });
+        assert!(
+            Filter::new("orders", Operator::All(Box::new(shipped.clone())))
+                .check(&empty)
+                .unwrap()
+        );
+        assert!(!Filter::new("orders", Operator::Any(Box::new(shipped)))
+            .check(&empty)
+            .unwrap());
+
+        assert!(matches!(
+            check("orders[0]", Operator::All(Box::new(positive))),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_is_subset_of() {
+        let granted = vec![json!("read"), json!("write"), json!(1)];
+        let check = |requested: Value| {
+            Filter::new("scopes", Operator::IsSubsetOf(granted.clone()))
+                .check(&json!({ "scopes": requested }))
+        };
+
+        assert!(check(json!(["read"])).unwrap());
+        assert!(check(json!(["write", "read", 1.0])).unwrap());
+        assert!(check(json!([])).unwrap());
+        assert!(!check(json!(["read", "write", 1, "admin"])).unwrap());
+        assert!(!check(json!(["admin", "delete"])).unwrap());
+        assert!(matches!(
+            check(json!("read")),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_count() {
+        let value = json!({
+            "none": [1, 2, 3],
+            "one": ["a", "b", "a"],
+            "several": [1, 1.0, 1, { "x": 1 }, { "x": 1 }, 2],
+            "label": "x",
+        });
+        let duplicates = |path: &str, op: CompareOp, count: usize| {
+            Filter::new(path, Operator::DuplicateCount { op, count }).check(&value)
+        };
+
+        assert!(duplicates("none", CompareOp::Eq, 0).unwrap());
+        assert!(duplicates("one", CompareOp::Eq, 1).unwrap());
+        assert!(duplicates("one", CompareOp::Le, 1).unwrap());
+        assert!(!duplicates("one", CompareOp::Lt, 1).unwrap());
+        assert!(duplicates("several", CompareOp::Eq, 3).unwrap());
+        assert!(duplicates("several", CompareOp::Gt, 2).unwrap());
+        assert!(!duplicates("several", CompareOp::Le, 2).unwrap());
+        assert!(duplicates("none", CompareOp::Ne, 1).unwrap());
+        assert!(duplicates("none", CompareOp::Ge, 0).unwrap());
+        assert!(matches!(
+            duplicates("label", CompareOp::Eq, 0),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_array_equals_ignoring_indices() {
+        let value = json!({ "row": ["2024-05-01T12:00:00Z", "alice", 3], "label": "x" });
+        let equals = |path: &str, target: Value, ignore: Vec<usize>| {
+            let target = target.as_array().unwrap().clone();
+            Filter::new(
+                path,
+                Operator::ArrayEqualsIgnoringIndices { target, ignore },
+            )
+            .check(&value)
+        };
+
+        assert!(equals("row", json!(["now", "alice", 3.0]), vec![0]).unwrap());
+        assert!(!equals("row", json!(["now", "alice", 3]), vec![]).unwrap());
+        assert!(!equals("row", json!(["now", "bob", 3]), vec![0]).unwrap());
+        assert!(!equals("row", json!(["now", "alice"]), vec![0, 2]).unwrap());
+        assert!(matches!(
+            equals("label", json!(["x"]), vec![]),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_length() {
+        let value = json!({
+            "name": "Zoë",
+            "tags": ["a", "b", "c", "d"],
+            "user": { "id": 1, "name": "John" },
+            "age": 25
+        });
+        let check = |path: &str, inner: Operator| {
+            Filter::new(path, Operator::Length(Box::new(inner))).check(&value)
+        };
+
+        // Characters, not bytes
+        assert!(check("name", Operator::Equals(json!(3))).unwrap());
+        assert!(check("tags", Operator::GreaterThan(3.0)).unwrap());
+        assert!(!check("tags", Operator::LessThan(4.0)).unwrap());
+        assert!(check("user", Operator::Equals(json!(2))).unwrap());
+
+        assert!(matches!(
+            check("age", Operator::Equals(json!(2))),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_flatten() {
+        let value = json!({ "grid": [[1, 2], [3, [4, 5]]], "flat": [6], "label": "x" });
+        let flatten = |path: &str, depth: usize, target: Value| {
+            Filter::new(
+                path,
+                Operator::Flatten {
+                    depth,
+                    inner: Box::new(Operator::ArrayContains(target)),
+                },
+            )
+            .check(&value)
+        };
+
+        assert!(!flatten("grid", 0, json!(3)).unwrap());
+        assert!(flatten("grid", 1, json!(3)).unwrap());
+        assert!(!flatten("grid", 1, json!(4)).unwrap());
+        assert!(flatten("grid", 1, json!([4, 5])).unwrap());
+        assert!(flatten("grid", 2, json!(4)).unwrap());
+        assert!(flatten("flat", 2, json!(6)).unwrap());
+        assert!(matches!(
+            flatten("label", 1, json!("x")),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_window() {
+        let value = json!({
+            "spike": [90, 120, 130, 110, 80],
+            "noisy": [120, 90, 130, 80, 140],
+            "short": [200, 200],
+            "label": "x",
+        });
+        let above =
+            |index: usize| Filter::new(format!("[{}]", index), Operator::GreaterThan(100.0));
+        let window = |path: &str, size: usize, quantifier: Quantifier| {
+            Filter::new(
+                path,
+                Operator::Window {
+                    size,
+                    inner: Box::new(Filter::new(
+                        ".",
+                        Operator::And((0..size).map(above).collect()),
+                    )),
+                    quantifier,
+                },
+            )
+            .check(&value)
+        };
+
+        assert!(window("spike", 3, Quantifier::Any).unwrap());
+        assert!(!window("noisy", 3, Quantifier::Any).unwrap());
+        assert!(!window("spike", 3, Quantifier::All).unwrap());
+        assert!(window("short", 2, Quantifier::All).unwrap());
+        assert!(!window("short", 3, Quantifier::Any).unwrap());
+        assert!(!window("short", 3, Quantifier::All).unwrap());
+        assert!(matches!(
+            window("label", 3, Quantifier::Any),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+
+        // The window's filter reads sub-arrays, so the whole array counts as read
+        let filter = Filter::new(
+            "short",
+            Operator::Window {
+                size: 3,
+                inner: Box::new(above(2)),
+                quantifier: Quantifier::Any,
+            },
+        );
+        assert_eq!(filter.referenced_paths(), vec!["short"]);
+        assert_eq!(
+            Value::Object(filter.project(&value)),
+            json!({ "short": [200, 200] })
+        );
+        assert!(filter.dry_run(&value).is_empty());
+        let invalid = Filter::new(
+            "short",
+            Operator::Window {
+                size: 3,
+                inner: Box::new(Filter::new("[", Operator::Exists(true))),
+                quantifier: Quantifier::Any,
+            },
+        );
+        assert!(matches!(
+            invalid.validate(),
+            Err(FilterError::InvalidPath(..))
+        ));
+    }
+
+    #[test]
+    fn test_object_operators() {
+        let value = json!({
+            "user": {
+                "id": 123,
+                "details": {
+                    "email": "john@example.com"
+                }
+            }
+        });
+
+        let filter = Filter::new("user", Operator::HasKey("id".to_string()));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new(
+            "user.details.email",
+            Operator::EndsWith("@example.com".to_string()),
+        );
+        assert!(filter.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_entries_matching() {
+        let value = json!({
+            "accounts": {
+                "user_1": { "status": "active" },
+                "user_2": { "status": "suspended" },
+                "user_3": { "status": "active" },
+                "admin_1": { "status": "suspended" }
+            }
+        });
+        let check = |key_glob: &str, status: &str, quantifier: Quantifier| {
+            Filter::new(
+                "accounts",
+                Operator::EntriesMatching {
+                    key_glob: key_glob.to_string(),
+                    inner: Box::new(Filter::new("status", Operator::Equals(json!(status)))),
+                    quantifier,
+                },
+            )
+            .check(&value)
+        };
+
+        assert!(check("user_*", "active", Quantifier::Any).unwrap());
+        assert!(!check("user_*", "active", Quantifier::All).unwrap());
+        assert!(check("user_?", "suspended", Quantifier::Any).unwrap());
+        assert!(check("*_1", "active", Quantifier::Any).unwrap());
+        assert!(check("admin_*", "suspended", Quantifier::All).unwrap());
+        assert!(!check("admin_*", "active", Quantifier::Any).unwrap());
+
+        // No matching keys
+        assert!(!check("guest_*", "active", Quantifier::Any).unwrap());
+        assert!(check("guest_*", "active", Quantifier::All).unwrap());
+
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("a*b*c", "aXbYbc"));
+        assert!(!glob_matches("a*b", "aXbY"));
+        assert!(!glob_matches("a?", "a"));
+    }
+
+    #[test]
+    fn test_type_is() {
+        let value = json!({
+            "null": null,
+            "bool": false,
+            "number": 1.5,
+            "string": "1.5",
+            "array": [],
+            "object": {}
+        });
+        let types = [
+            ("null", JsonType::Null),
+            ("bool", JsonType::Bool),
+            ("number", JsonType::Number),
+            ("string", JsonType::String),
+            ("array", JsonType::Array),
+            ("object", JsonType::Object),
+        ];
+
+        for (path, _) in types {
+            for (other, json_type) in types {
+                let filter = Filter::new(path, Operator::TypeIs(json_type));
+                assert_eq!(filter.check(&value).unwrap(), path == other, "{}", path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let value = json!({
+            "age": 25,
+            "name": "John Doe"
+        });
+
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(20.0)),
+                Filter::new("name", Operator::StartsWith("John".to_string())),
+            ]),
+        );
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new(
+            ".",
+            Operator::Or(vec![
+                Filter::new("age", Operator::GreaterThan(30.0)),
+                Filter::new("name", Operator::Contains("John".to_string())),
+            ]),
+        );
+        assert!(filter.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_not() {
+        let value = json!({
+            "age": 25,
+            "name": "John Doe"
+        });
+
+        let not_admin = |min_age: f64| {
+            Filter::new(
+                ".",
+                Operator::And(vec![
+                    Filter::new(
+                        ".",
+                        Operator::Not(Box::new(Filter::new(
+                            "name",
+                            Operator::StartsWith("Admin".to_string()),
+                        ))),
+                    ),
+                    Filter::new("age", Operator::GreaterThan(min_age)),
+                ]),
+            )
+        };
+        assert!(not_admin(20.0).check(&value).unwrap());
+        assert!(!not_admin(30.0).check(&value).unwrap());
+        assert!(!not_admin(20.0)
+            .check(&json!({ "age": 25, "name": "Admin Bob" }))
+            .unwrap());
+
+        // Errors from the negated filter aren't turned into `true`
+        assert!(matches!(
+            not_admin(20.0).check(&json!({ "age": 25 })),
+            Err(FilterError::PathNotFound(..))
+        ));
+        assert!(matches!(
+            not_admin(20.0).check(&json!({ "age": 25, "name": 7 })),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        // Far deeper than recursive evaluation could handle
+        let nest = |leaf: Filter, wrap: fn(usize, Filter) -> Operator| {
+            (0..5_000).fold(leaf, |filter, level| Filter::new(".", wrap(level, filter)))
+        };
+        let and_or = |level: usize, filter: Filter| {
+            if level.is_multiple_of(2) {
+                Operator::And(vec![filter, Filter::new("b", Operator::Equals(json!(2)))])
+            } else {
+                Operator::Or(vec![Filter::new("b", Operator::Equals(json!(3))), filter])
+            }
+        };
+        let value = json!({ "a": 1, "b": 2 });
+
+        let filter = nest(Filter::new("a", Operator::Equals(json!(1))), and_or);
+        assert!(filter.check(&value).unwrap());
+        let filter = nest(Filter::new("a", Operator::Equals(json!(2))), and_or);
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = nest(Filter::new("a", Operator::Equals(json!(1))), |_, filter| {
+            Operator::Not(Box::new(filter))
+        });
+        assert!(filter.check(&value).unwrap());
+
+        let filter = nest(Filter::new("c", Operator::Equals(json!(1))), and_or);
+        assert!(matches!(
+            filter.check_detailed(&value),
+            Err(DetailedError {
+                error: FilterError::PathNotFound(..),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_filter_builder() {
+        let built = FilterBuilder::path("age")
+            .greater_than(20.0)
+            .and(FilterBuilder::path("name").starts_with("John"))
+            .and(
+                FilterBuilder::path("role")
+                    .equals("admin")
+                    .or(FilterBuilder::path("tags").array_contains("staff")),
+            )
+            .and(FilterBuilder::path("banned").exists().not())
+            .build();
+
+        let expected = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(20.0)),
+                Filter::new("name", Operator::StartsWith("John".to_string())),
+                Filter::new(
+                    ".",
+                    Operator::Or(vec![
+                        Filter::new("role", Operator::Equals(json!("admin"))),
+                        Filter::new("tags", Operator::ArrayContains(json!("staff"))),
+                    ]),
+                ),
+                Filter::new(
+                    ".",
+                    Operator::Not(Box::new(Filter::new("banned", Operator::Exists(true)))),
+                ),
+            ]),
+        );
+        assert_eq!(built, expected);
+        assert_eq!(
+            serde_json::to_value(&built).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+
+        let value = json!({ "age": 25, "name": "John Doe", "role": "user", "tags": ["staff"] });
+        assert!(built.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_simplify() {
+        let not = |filter: Filter| Filter::new(".", Operator::Not(Box::new(filter)));
+        let adult = Filter::new("age", Operator::GreaterOrEqual(18.0));
+        let john = Filter::new("name", Operator::Equals(json!("John")));
+        let values = [
+            json!({ "age": 25, "name": "John" }),
+            json!({ "age": 25, "name": "Jane" }),
+            json!({ "age": 12, "name": "John" }),
+            json!({ "age": 12, "name": "Jane" }),
+        ];
+        let assert_simplifies = |filter: Filter, expected: Filter| {
+            let simplified = filter.clone().simplify();
+            assert_eq!(simplified, expected);
+            for value in &values {
+                assert_eq!(
+                    filter.check(value).unwrap(),
+                    simplified.check(value).unwrap(),
+                    "{:?} on {}",
+                    filter,
+                    value
+                );
+            }
+        };
+
+        assert_simplifies(not(not(adult.clone())), adult.clone());
+        assert_simplifies(not(not(not(adult.clone()))), not(adult.clone()));
+        assert_simplifies(
+            not(Filter::new(
+                ".",
+                Operator::And(vec![not(adult.clone()), not(john.clone())]),
+            )),
+            Filter::new(".", Operator::Or(vec![adult.clone(), john.clone()])),
+        );
+        assert_simplifies(
+            not(Filter::new(
+                ".",
+                Operator::Or(vec![not(adult.clone()), john.clone()]),
+            )),
+            Filter::new(".", Operator::And(vec![adult.clone(), not(john.clone())])),
+        );
+
+        // Nested filters are simplified too
+        assert_simplifies(
+            Filter::new(
+                ".",
+                Operator::And(vec![not(not(adult.clone())), john.clone()]),
+            ),
+            Filter::new(".", Operator::And(vec![adult.clone(), john.clone()])),
+        );
+
+        // Not beneficial, or not equivalent with another path
+        let filter = not(Filter::new(".", Operator::And(vec![adult.clone(), john])));
+        assert_simplifies(filter.clone(), filter);
+        let filter = not(Filter::new("user", Operator::Not(Box::new(adult.clone()))));
+        assert_eq!(filter.clone().simplify(), filter);
+        let filter = Filter::new("user", Operator::Not(Box::new(not(adult))));
+        assert_eq!(filter.clone().simplify(), filter);
+    }
+
+    #[test]
+    fn test_exists() {
+        let value = json!({ "user": { "details": { "email": "john@example.com", "fax": null } } });
+        let check =
+            |path: &str, exists: bool| Filter::new(path, Operator::Exists(exists)).check(&value);
+
+        assert!(check("user.details.phone", false).unwrap());
+        assert!(!check("user.details.phone", true).unwrap());
+        assert!(check("user.details.email", true).unwrap());
+        assert!(!check("user.details.email", false).unwrap());
+        assert!(check("user.details.fax", true).unwrap());
+        assert!(check("account.details.phone", false).unwrap());
+
+        let compiled = Filter::new("user.details.phone", Operator::Exists(false)).compile();
+        assert!(compiled.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_fields() {
+        let fields = |missing_is_error: bool| {
+            Filter::new(
+                "user",
+                Operator::Fields {
+                    fields: BTreeMap::from([
+                        ("age".to_string(), Operator::GreaterOrEqual(18.0)),
+                        (
+                            "email".to_string(),
+                            Operator::EndsWith("@example.com".to_string()),
+                        ),
+                    ]),
+                    missing_is_error,
+                },
+            )
+        };
+
+        let value = json!({ "user": { "age": 30, "email": "john@example.com", "name": "John" } });
+        assert!(fields(false).check(&value).unwrap());
+
+        let value = json!({ "user": { "age": 16, "email": "john@example.com" } });
+        assert!(!fields(false).check(&value).unwrap());
+        let value = json!({ "user": { "age": 30, "email": "john@other.org" } });
+        assert!(!fields(false).check(&value).unwrap());
+
+        let value = json!({ "user": { "age": 30 } });
+        assert!(!fields(false).check(&value).unwrap());
+        assert!(matches!(
+            fields(true).check(&value),
+            Err(FilterError::PathNotFound(key)) if key == "email"
+        ));
+
+        assert!(matches!(
+            fields(false).check(&json!({ "user": [] })),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_optional_match() {
+        let filter = Filter::new(
+            "user.email",
+            Operator::OptionalMatch(Box::new(Operator::EndsWith("@example.com".to_string()))),
+        );
+
+        assert!(filter.check(&json!({ "user": {} })).unwrap());
+        assert!(filter.check(&json!({})).unwrap());
+        assert!(filter
+            .check(&json!({ "user": { "email": "john@example.com" } }))
+            .unwrap());
+        assert!(!filter
+            .check(&json!({ "user": { "email": "john@other.org" } }))
+            .unwrap());
+        assert!(matches!(
+            filter.check(&json!({ "user": { "email": 1 } })),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+
+        let compiled = filter.compile();
+        assert!(compiled.check(&json!({ "user": {} })).unwrap());
+        assert!(!compiled
+            .check(&json!({ "user": { "email": "john@other.org" } }))
+            .unwrap());
+    }
+
+    #[test]
+    fn test_or_false_on_mismatch() {
+        let filter = Filter::new(
+            "age",
+            Operator::OrFalseOnMismatch(Box::new(Operator::GreaterThan(20.0))),
+        );
+
+        assert!(filter.check(&json!({ "age": 25 })).unwrap());
+        assert!(!filter.check(&json!({ "age": 15 })).unwrap());
+        assert!(!filter.check(&json!({ "age": "25" })).unwrap());
+        assert!(matches!(
+            filter.check(&json!({})),
+            Err(FilterError::PathNotFound(..))
+        ));
+
+        let filter = Filter::new(
+            "age",
+            Operator::OptionalMatch(Box::new(Operator::OrFalseOnMismatch(Box::new(
+                Operator::GreaterThan(20.0),
+            )))),
+        );
+        assert!(filter.check(&json!({})).unwrap());
+        assert!(!filter.check(&json!({ "age": "25" })).unwrap());
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let value = json!({ "age": "25" }); // age is a string, not a number
+
+        let filter = Filter::new("age", Operator::GreaterThan(20.0));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_path_not_found() {
+        let value = json!({ "name": "John" });
+
+        let filter = Filter::new("age", Operator::GreaterThan(20.0));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::PathNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_in() {
+        let value = json!({ "status": "active", "code": 2, "ratio": 0.5, "tags": ["a"] });
+        let one_of = |path: &str, candidates: Value| {
+            let candidates = candidates.as_array().unwrap().clone();
+            Filter::new(path, Operator::In(candidates)).check(&value)
+        };
+
+        assert!(one_of("status", json!(["active", "pending"])).unwrap());
+        assert!(!one_of("status", json!(["deleted", "ACTIVE"])).unwrap());
+        assert!(one_of("code", json!([1, 2, 3])).unwrap());
+        assert!(one_of("code", json!([2.0])).unwrap());
+        assert!(!one_of("code", json!(["2"])).unwrap());
+        assert!(one_of("ratio", json!([0.25, 0.5])).unwrap());
+        assert!(one_of("tags", json!([["a"], ["b"]])).unwrap());
+        assert!(!one_of("status", json!([])).unwrap());
+    }
+
+    #[test]
+    fn test_in_path() {
+        let value = json!({
+            "user": { "role": "editor", "level": 3 },
+            "allowedRoles": ["admin", "editor"],
+            "limits": { "max": 5 }
+        });
+
+        let filter = Filter::new("user.role", Operator::InPath("allowedRoles".to_string()));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("user.level", Operator::InPath("allowedRoles".to_string()));
+        assert!(!filter.check(&value).unwrap());
+
+        // Nested filters resolve the list relative to their own scope
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![Filter::new(
+                "user.role",
+                Operator::InPath("allowedRoles".to_string()),
+            )]),
+        );
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("user.role", Operator::InPath("limits".to_string()));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+
+        let filter = Filter::new("user.role", Operator::InPath("missing".to_string()));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::PathNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_member_of_path() {
+        let valid = json!({ "status": "shipped", "validStatuses": ["pending", "shipped"] });
+        let invalid = json!({ "status": "lost", "validStatuses": ["pending", "shipped"] });
+
+        let filter: Filter = serde_json::from_value(json!({
+            "path": "status",
+            "operator": { "MemberOfPath": "validStatuses" }
+        }))
+        .unwrap();
+        assert_eq!(
+            filter,
+            Filter::new("status", Operator::InPath("validStatuses".to_string()))
+        );
+        assert!(filter.check(&valid).unwrap());
+        assert!(!filter.check(&invalid).unwrap());
+    }
+
+    #[test]
+    fn test_retain_matching() {
+        let users = vec![
+            json!({ "name": "John", "age": 25 }),
+            json!({ "name": "Jane", "age": 17 }),
+            json!({ "name": "Jim" }),
+            json!({ "name": "Joe", "age": 40 }),
+        ];
+        let filter = Filter::new("age", Operator::GreaterThan(18.0));
+
+        assert_eq!(
+            filter.retain_matching(&users),
+            vec![users[0].clone(), users[3].clone()]
+        );
+        assert!(matches!(
+            filter.try_retain_matching(&users),
+            Err(FilterError::PathNotFound(..))
+        ));
+        assert_eq!(
+            filter.try_retain_matching(&users[..2]).unwrap(),
+            vec![users[0].clone()]
+        );
+    }
+
+    #[test]
+    fn test_count_matches() {
+        let value = json!({
+            "orders": [
+                { "total": 10, "status": "shipped" },
+                { "total": 25, "status": "pending" },
+                { "total": 40, "status": "shipped" }
+            ],
+            "customer": { "name": "John", "returns": [{ "status": "shipped" }] }
+        });
+
+        let root = Filter::new(".", Operator::Exists(true));
+        let shipped = Filter::new("status", Operator::Equals(json!("shipped")));
+        assert_eq!(root.count_matches(&value, "orders", &shipped).unwrap(), 2);
+
+        let large = Filter::new("total", Operator::GreaterThan(100.0));
+        assert_eq!(root.count_matches(&value, "orders", &large).unwrap(), 0);
+
+        // Relative to the filter's path, whose operator isn't a precondition
+        let customer = Filter::new("customer", Operator::Equals(json!("Jane")));
+        assert_eq!(
+            customer.count_matches(&value, "returns", &shipped).unwrap(),
+            1
+        );
+
+        assert!(matches!(
+            root.count_matches(&value, "customer", &shipped),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            customer.count_matches(&value, "orders", &shipped),
+            Err(FilterError::PathNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_matches_elements() {
+        let records = json!([
+            { "id": 1, "status": "ok", "size": 10 },
+            { "id": 2, "status": "failed", "size": 20 },
+        ]);
+
+        let filter = Filter::new("status", Operator::Equals(json!("failed")));
+        assert!(filter.matches_any_element(&records).unwrap());
+        assert!(!filter.matches_all_elements(&records).unwrap());
+
+        let filter = Filter::new("size", Operator::GreaterThan(5.0));
+        assert!(filter.matches_any_element(&records).unwrap());
+        assert!(filter.matches_all_elements(&records).unwrap());
+
+        assert!(!filter.matches_any_element(&json!([])).unwrap());
+        assert!(filter.matches_all_elements(&json!([])).unwrap());
+
+        assert!(matches!(
+            filter.matches_any_element(&json!({ "size": 10 })),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_shared_across_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Filter>();
+        assert_send_sync::<CompiledFilter>();
+
+        let document = Arc::new(json!({ "items": [{ "price": 5 }, { "price": 15 }] }));
+        let filter = Filter::new("items.*.price", Operator::GreaterThan(10.0));
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    let document = Arc::clone(&document);
+                    let filter = &filter;
+                    scope.spawn(move || filter.check(&document).unwrap())
+                })
+                .collect();
+            for handle in handles {
+                assert!(handle.join().unwrap());
+            }
+        });
+    }
+
+    #[test]
+    fn test_check_serializable() {
+        #[derive(Serialize)]
+        struct Address {
+            city: String,
+        }
+
+        #[derive(Serialize)]
+        struct User {
+            name: String,
+            age: u32,
+            address: Address,
+        }
+
+        let user = User {
+            name: "John".to_string(),
+            age: 30,
+            address: Address {
+                city: "Berlin".to_string(),
+            },
+        };
+
+        let filter = Filter::new("address.city", Operator::Equals(json!("Berlin")));
+        assert!(filter.check_serializable(&user).unwrap());
+        let filter = Filter::new("age", Operator::GreaterThan(40.0));
+        assert!(!filter.check_serializable(&user).unwrap());
+        let filter = Filter::new("name", Operator::GreaterThan(40.0));
+        assert!(matches!(
+            filter.check_serializable(&user),
+            Err(CheckError::Filter(FilterError::TypeMismatch { .. }))
+        ));
+
+        let unrepresentable = HashMap::from([((1, 2), "pair")]);
+        assert!(matches!(
+            filter.check_serializable(&unrepresentable),
+            Err(CheckError::Parse(..))
+        ));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_check_cbor() {
+        let value = json!({
+            "user": { "name": "John", "age": 25, "tags": ["admin", "dev"] },
+            "score": 9.5
+        });
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&value, &mut bytes).unwrap();
+
+        for filter in [
+            Filter::new("user.age", Operator::GreaterThan(18.0)),
+            Filter::new("user.tags", Operator::ArrayContains(json!("dev"))),
+            Filter::new("score", Operator::LessThan(5.0)),
+            Filter::new("user.name", Operator::StartsWith("Ja".to_string())),
+        ] {
+            assert_eq!(
+                filter.check_cbor(&bytes).unwrap(),
+                filter.check(&value).unwrap()
+            );
+        }
+
+        let filter = Filter::new("user.email", Operator::Exists(true));
+        assert!(!filter.check_cbor(&bytes).unwrap());
+        assert!(matches!(
+            filter.check_cbor(&bytes[..bytes.len() - 1]),
+            Err(CheckError::Cbor(..))
+        ));
+    }
+
+    #[test]
+    fn test_annotate_ndjson() {
+        let input = concat!(
+            r#"{"id":1,"age":30}"#,
+            "\n",
+            r#"{"id":2,"age":12}"#,
+            "\n",
+            r#"{"id":3,"age":"old"}"#,
+            "\n",
+            "not json\n",
+        );
+        let filter = Filter::new("age", Operator::GreaterOrEqual(18.0));
+
+        let mut output = Vec::new();
+        let matched = filter
+            .annotate_ndjson(input.as_bytes(), &mut output, "adult")
+            .unwrap();
+        assert_eq!(matched, 1);
+
+        let lines: Vec<&str> = std::str::from_utf8(&output).unwrap().lines().collect();
+        assert_eq!(lines.len(), 4);
+        let records: Vec<Value> = lines[..3]
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records[0], json!({ "id": 1, "age": 30, "adult": true }));
+        assert_eq!(records[1], json!({ "id": 2, "age": 12, "adult": false }));
+        assert_eq!(records[2], json!({ "id": 3, "age": "old", "adult": null }));
+        assert_eq!(lines[3], "not json");
+    }
+
+    #[test]
+    fn test_explain() {
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(20.0)),
+                Filter::new("name", Operator::StartsWith("John".to_string())),
+                Filter::new("tags", Operator::ArrayContains(json!("admin"))),
+                Filter::new("score", Operator::LessThan(100.0)),
+            ]),
+        );
+        let value = json!({ "age": 25, "name": "John", "tags": ["dev"], "score": 50 });
+
+        let trace = filter.explain(&value);
+        assert_eq!(trace.result, Some(false));
+        let children = &trace.branches[0].children;
+        assert_eq!(children.len(), 4);
+        let failing: Vec<_> = children
+            .iter()
+            .filter(|child| child.result != Some(true))
+            .collect();
+        assert_eq!(failing.len(), 1);
+        assert_eq!(failing[0].path, "tags");
+        assert_eq!(failing[0].operator, "ArrayContains");
+        assert_eq!(failing[0].branches[0].value, r#"["dev"]"#);
+
+        let trace = Filter::new("age", Operator::StartsWith("2".to_string())).explain(&value);
+        assert_eq!(trace.result, None);
+        assert!(trace.error.unwrap().starts_with("Type mismatch"));
+
+        // Combined in order like `check`, while still tracing every child
+        let value = json!({ "age": 25 });
+        let age = |min: f64| Filter::new("age", Operator::GreaterThan(min));
+        let missing = || Filter::new("missing", Operator::Equals(json!(1)));
+        for filter in [
+            Filter::new(".", Operator::Or(vec![age(20.0), missing()])),
+            Filter::new(".", Operator::Or(vec![age(30.0), missing()])),
+            Filter::new(".", Operator::Or(vec![missing(), age(20.0)])),
+            Filter::new(".", Operator::And(vec![age(30.0), missing()])),
+            Filter::new(".", Operator::And(vec![age(20.0), missing()])),
+            Filter::new(".", Operator::And(vec![missing(), age(30.0)])),
+        ] {
+            let trace = filter.explain(&value);
+            assert_eq!(trace.result, filter.check(&value).ok(), "{:?}", filter);
+            assert_eq!(trace.branches[0].children.len(), 2);
+        }
+        let filter = Filter::new(".", Operator::Or(vec![age(20.0), missing()]));
+        assert_eq!(filter.explain(&value).result, Some(true));
+    }
+
+    #[test]
+    fn test_explain_json() {
+        let filter = Filter::new(
+            "user",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(18.0)),
+                Filter::new(
+                    ".",
+                    Operator::Not(Box::new(Filter::new(
+                        "name",
+                        Operator::StartsWith("J".to_string()),
+                    ))),
+                ),
+            ]),
+        );
+        let value = json!({ "user": { "age": 25, "name": "John" } });
+
+        assert_eq!(
+            filter.explain_json(&value),
+            json!({
+                "path": "user",
+                "operator": "And",
+                "result": false,
+                "branches": [{
+                    "path": "user",
+                    "value": r#"{"age":25,"name":"John"}"#,
+                    "result": false,
+                    "children": [
+                        {
+                            "path": "age",
+                            "operator": "GreaterThan",
+                            "result": true,
+                            "branches": [{ "path": "age", "value": "25", "result": true }]
+                        },
+                        {
+                            "path": ".",
+                            "operator": "Not",
+                            "result": false,
+                            "branches": [{
+                                "path": ".",
+                                "value": r#"{"age":25,"name":"John"}"#,
+                                "result": false,
+                                "children": [{
+                                    "path": "name",
+                                    "operator": "StartsWith",
+                                    "result": true,
+                                    "branches": [{ "path": "name", "value": r#""John""#, "result": true }]
+                                }]
+                            }]
+                        }
+                    ]
+                }]
+            })
+        );
+        assert!(!filter.check(&value).unwrap());
+
+        let trace = filter.explain_json(&json!({ "user": { "name": "John" } }));
+        assert_eq!(trace["result"], Value::Null);
+        assert_eq!(
+            trace["branches"][0]["children"][0]["error"],
+            json!("Path not found: age")
+        );
+
+        let trace = Filter::new("text", Operator::Contains("a".to_string()))
+            .explain_json(&json!({ "text": "b".repeat(100) }));
+        assert_eq!(
+            trace["branches"][0]["value"],
+            json!(format!("\"{}...", "b".repeat(63)))
+        );
+    }
+
+    #[test]
+    fn test_check_detailed() {
+        let value = json!({ "user": { "age": "old", "name": "John" } });
+        let filter = Filter::new(
+            "user",
+            Operator::And(vec![
+                Filter::new("name", Operator::StartsWith("J".to_string())),
+                Filter::new(
+                    ".",
+                    Operator::Or(vec![Filter::new("age", Operator::GreaterThan(18.0))]),
+                ),
+            ]),
+        );
+
+        let error = filter.check_detailed(&value).unwrap_err();
+        assert_eq!(error.path, "user.age");
+        assert_eq!(error.operator, "GreaterThan");
+        assert!(matches!(error.error, FilterError::TypeMismatch { .. }));
+
+        let error = Filter::new("user.email", Operator::EndsWith(".com".to_string()))
+            .check_detailed(&value)
+            .unwrap_err();
+        assert_eq!(error.path, "user.email");
+        assert_eq!(error.operator, "EndsWith");
+        assert!(matches!(error.error, FilterError::PathNotFound(..)));
+
+        let value = json!({ "user": { "age": 30, "name": "John" } });
+        assert!(filter.check_detailed(&value).unwrap());
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(Filter::try_new("age", Operator::GreaterThan(18.0)).is_ok());
+        assert!(matches!(
+            Filter::try_new("age", Operator::GreaterThan(f64::NAN)),
+            Err(FilterError::InvalidOperand(..))
+        ));
+        assert!(matches!(
+            Filter::try_new("a..b", Operator::GreaterThan(18.0)),
+            Err(FilterError::InvalidPath(..))
+        ));
+        assert!(matches!(
+            Filter::try_new("role", Operator::InPath("roles[".to_string())),
+            Err(FilterError::InvalidPath(..))
+        ));
+
+        // Nested filters are validated too
+        let operator = Operator::Or(vec![
+            Filter::new("a", Operator::Equals(json!(1))),
+            Filter::new(
+                "b",
+                Operator::OptionalMatch(Box::new(Operator::Range {
+                    min: Some(f64::NAN),
+                    max: None,
+                    min_inclusive: true,
+                    max_inclusive: true,
+                })),
+            ),
+        ]);
+        assert!(matches!(
+            Filter::try_new(".", operator),
+            Err(FilterError::InvalidOperand(..))
+        ));
+    }
+
+    #[test]
+    fn test_check_lenient() {
+        let value = json!({ "name": "John", "tags": ["a"], "age": "unknown" });
+
+        let filter = Filter::new("email", Operator::EndsWith("@example.com".to_string()));
+        assert!(filter.check(&value).is_err());
+        assert!(!filter.check_lenient(&value).unwrap());
+
+        let filter = Filter::new("tags[3]", Operator::Equals(json!("a")));
+        assert!(!filter.check_lenient(&value).unwrap());
+
+        let filter = Filter::new("age", Operator::GreaterThan(18.0));
+        assert!(!filter.check_lenient(&value).unwrap());
+
+        // Failing children don't abort their parent
+        let filter = Filter::new(
+            ".",
+            Operator::Or(vec![
+                Filter::new("email", Operator::Contains("@".to_string())),
+                Filter::new("age", Operator::GreaterThan(18.0)),
+                Filter::new("name", Operator::Equals(json!("John"))),
+            ]),
+        );
+        assert!(filter.check(&value).is_err());
+        assert!(filter.check_lenient(&value).unwrap());
+
+        let filter = Filter::new(
+            ".",
+            Operator::Not(Box::new(Filter::new(
+                "email",
+                Operator::Contains("@".to_string()),
+            ))),
+        );
+        assert!(filter.check_lenient(&value).unwrap());
+    }
+
+    #[test]
+    fn test_interned_paths() {
+        let value = json!({ "user": { "age": 25 } });
+        let mut interner = PathInterner::new();
+
+        let a = Filter::new_interned(interner.intern("user.age"), Operator::GreaterThan(20.0));
+        let b = Filter::new_interned(interner.intern("user.age"), Operator::LessThan(20.0));
+        assert!(Arc::ptr_eq(&a.path, &b.path));
+
+        let plain = Filter::new("user.age", Operator::GreaterThan(20.0));
+        assert_eq!(a, plain);
+        assert_eq!(a.check(&value).unwrap(), plain.check(&value).unwrap());
+        assert!(!b.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_deep_equals() {
+        let value = json!({
+            "doc": { "tags": ["a", "b", "a"], "nested": { "ids": [[1, 2], [3]] } }
+        });
+        let reordered = json!({ "nested": { "ids": [[3], [2, 1]] }, "tags": ["b", "a", "a"] });
+
+        let filter = Filter::new(
+            "doc",
+            Operator::DeepEquals {
+                target: reordered.clone(),
+                ignore_array_order: true,
+            },
+        );
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new(
+            "doc",
+            Operator::DeepEquals {
+                target: reordered,
+                ignore_array_order: false,
+            },
+        );
+        assert!(!filter.check(&value).unwrap());
+
+        // Multiset semantics: duplicates must match up
+        let filter = Filter::new(
+            "doc.tags",
+            Operator::DeepEquals {
+                target: json!(["a", "b", "b"]),
+                ignore_array_order: true,
+            },
+        );
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new(
+            "doc.nested",
+            Operator::DeepEquals {
+                target: json!({ "ids": "other" }),
+                ignore_array_order: true,
+            },
+        );
+        assert!(!filter.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_referenced_paths() {
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(20.0)),
+                Filter::new(
+                    "user",
+                    Operator::Or(vec![
+                        Filter::new("name", Operator::StartsWith("J".to_string())),
+                        Filter::new(
+                            ".",
+                            Operator::Not(Box::new(Filter::new(
+                                "email",
+                                Operator::EndsWith(".com".to_string()),
+                            ))),
+                        ),
+                    ]),
+                ),
+                Filter::new("items[0].price", Operator::GreaterThan(6.0)),
+                Filter::new("age", Operator::LessThan(65.0)),
+            ]),
+        );
+
+        assert_eq!(
+            filter.referenced_paths(),
+            vec!["age", "user.name", "user.email", "items[0].price"]
+        );
+        assert_eq!(
+            Filter::new(".", Operator::Exists(true)).referenced_paths(),
+            vec!["."]
+        );
+    }
+
+    #[test]
+    fn test_referenced_operand_paths() {
+        let nested = |operator: Operator| {
+            Filter::new(
+                "order",
+                Operator::And(vec![Filter::new("status", operator)]),
+            )
+            .referenced_paths()
+        };
+
+        // Resolved against the same value as the filter's own path
+        assert_eq!(
+            nested(Operator::InPath("valid".to_string())),
+            vec!["order.status", "order.valid"]
+        );
+        assert_eq!(
+            nested(Operator::SameAs("expected".to_string())),
+            vec!["order.status", "order.expected"]
+        );
+        assert_eq!(
+            nested(Operator::MultipleOfPath("step".to_string())),
+            vec!["order.status", "order.step"]
+        );
+        assert_eq!(
+            nested(Operator::OptionalMatch(Box::new(Operator::InPath(
+                "valid".to_string()
+            )))),
+            vec!["order.status", "order.valid"]
+        );
+
+        // Relative to the filter's own path
+        assert_eq!(
+            Filter::new(
+                "invoice",
+                Operator::AggregateEqualsPath {
+                    array_path: "lineItems".to_string(),
+                    reducer: Reducer::Sum,
+                    field: "amount".to_string(),
+                    other_path: "total".to_string(),
+                },
+            )
+            .referenced_paths(),
+            vec![
+                "invoice",
+                "invoice.lineItems",
+                "invoice.lineItems[].amount",
+                "invoice.total"
+            ]
+        );
+        let fields = Operator::Fields {
+            fields: BTreeMap::from([
+                ("age".to_string(), Operator::GreaterThan(18.0)),
+                ("a.b".to_string(), Operator::InPath("allowed".to_string())),
+            ]),
+            missing_is_error: false,
+        };
+        assert_eq!(
+            Filter::new("user", fields).referenced_paths(),
+            vec!["user", r#"user["a.b"]"#, "allowed", "user.age"]
+        );
+    }
+
+    #[cfg(feature = "geo")]
+    #[test]
+    fn test_referenced_coordinate_paths() {
+        let filter = Filter::new(
+            "store",
+            Operator::WithinDistance {
+                lat: 0.0,
+                lng: 0.0,
+                km: 1.0,
+                lat_path: "location.lat".to_string(),
+                lng_path: "location.lng".to_string(),
+            },
+        );
+        assert_eq!(
+            filter.referenced_paths(),
+            vec!["store", "store.location.lat", "store.location.lng"]
+        );
+    }
+
+    #[cfg(feature = "datetime")]
+    #[test]
+    fn test_referenced_duration_paths() {
+        let filter = Filter::new(
+            "order",
+            Operator::And(vec![Filter::new(
+                "shipped_at",
+                Operator::WithinDurationOf {
+                    other_path: "created_at".to_string(),
+                    duration_secs: 60,
+                },
+            )]),
+        );
+        assert_eq!(
+            filter.referenced_paths(),
+            vec!["order.shipped_at", "order.created_at"]
+        );
+    }
+
+    #[test]
+    fn test_project() {
+        let value = json!({
+            "age": 25,
+            "user": { "name": "John" },
+            "items": [{ "price": 5, "ok": true }, { "price": 7, "ok": true }]
+        });
+
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(20.0)),
+                Filter::new(
+                    "user",
+                    Operator::Or(vec![
+                        Filter::new("name", Operator::StartsWith("J".to_string())),
+                        Filter::new("email", Operator::EndsWith(".com".to_string())),
+                    ]),
+                ),
+                Filter::new("items[?ok=true].price", Operator::GreaterThan(6.0)),
+                Filter::new("missing.deep", Operator::Equals(json!(1))),
+            ]),
+        );
+
+        let projection = filter.project(&value);
+        assert_eq!(
+            Value::Object(projection),
+            json!({
+                "age": 25,
+                "user.name": "John",
+                "user.email": null,
+                "items[?ok=true].price": [5, 7],
+                "missing.deep": null
+            })
+        );
+    }
+
+    #[test]
+    fn test_same_as() {
+        let value = json!({
+            "password": "hunter2",
+            "confirm": "hunter2",
+            "other": "hunter3",
+            "total": 10,
+            "sum": 10.0
+        });
+
+        let filter = Filter::new("confirm", Operator::SameAs("password".to_string()));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("other", Operator::SameAs("password".to_string()));
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new("total", Operator::SameAs("sum".to_string()));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("confirm", Operator::SameAs("missing".to_string()));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::PathNotFound(..))
+        ));
+        let filter = Filter::new("missing", Operator::SameAs("password".to_string()));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::PathNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_hash_key() {
+        let build = |age: f64| {
+            Filter::new(
+                ".",
+                Operator::And(vec![
+                    Filter::new("age", Operator::GreaterThan(age)),
+                    Filter::new("tags", Operator::ArrayContains(json!({ "a": 1, "b": 2 }))),
+                ]),
+            )
+        };
+
+        assert_eq!(build(20.0).hash_key(), build(20.0).hash_key());
+        assert_ne!(build(20.0).hash_key(), build(21.0).hash_key());
+        assert_ne!(
+            Filter::new("a", Operator::Equals(json!(1))).hash_key(),
+            Filter::new("b", Operator::Equals(json!(1))).hash_key()
+        );
+
+        // Equal filters hash equal, and `-0.0 == 0.0`.
+        let zero = Filter::new("a", Operator::GreaterThan(0.0));
+        let negative_zero = Filter::new("a", Operator::GreaterThan(-0.0));
+        assert_eq!(zero, negative_zero);
+        assert_eq!(zero.hash_key(), negative_zero.hash_key());
+        // Arbitrary precision numbers compare as written, so these differ there
+        #[cfg(not(feature = "arbitrary-precision"))]
+        {
+            let zero = Filter::new("a", Operator::Equals(json!(0.0)));
+            let negative_zero = Filter::new("a", Operator::Equals(json!(-0.0)));
+            assert_eq!(zero, negative_zero);
+            assert_eq!(zero.hash_key(), negative_zero.hash_key());
+        }
+    }
+
+    #[test]
+    fn test_predicate_path_segments() {
+        let value = json!({
+            "items": [
+                { "id": 1, "status": "inactive", "price": 5 },
+                { "id": 2, "status": "active", "price": 10 },
+                { "id": 3, "status": "active", "price": 30 }
+            ]
+        });
+
+        // First match only
+        let filter = Filter::new("items[status=active].price", Operator::LessThan(20.0));
+        assert!(filter.check(&value).unwrap());
+        let filter = Filter::new("items[id=3].price", Operator::GreaterThan(20.0));
+        assert!(filter.check(&value).unwrap());
+
+        // All matches, distributed with any/all quantification
+        let filter = Filter::new("items[?status=active].price", Operator::GreaterThan(20.0));
+        assert!(filter.check(&value).unwrap());
+        assert!(!filter.check_quantified(&value, Quantifier::All).unwrap());
+        assert_eq!(
+            filter.matching_paths(&value).unwrap(),
+            vec!["items[2].price".to_string()]
+        );
+
+        let filter = Filter::new("items[?status=active].price", Operator::GreaterThan(8.0));
+        assert!(filter.check_quantified(&value, Quantifier::All).unwrap());
+        assert_eq!(
+            filter.matching_paths(&value).unwrap(),
+            vec!["items[1].price".to_string(), "items[2].price".to_string()]
+        );
+
+        let filter = Filter::new("items[?status=deleted].price", Operator::GreaterThan(0.0));
+        assert!(!filter.check(&value).unwrap());
+        let filter = Filter::new("items[status=deleted].price", Operator::GreaterThan(0.0));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::PathNotFound(..))
+        ));
+
+        // Ordering comparisons need a quoted key, so unquoted keys keep their
+        // meaning up to the first `=`
+        let filter = Filter::new(r#"items[?"price">=10].id"#, Operator::GreaterThan(1.0));
+        assert!(filter.check_quantified(&value, Quantifier::All).unwrap());
+        let filter = Filter::new(
+            r#"items["status"!="active"].id"#,
+            Operator::Equals(json!(1)),
+        );
+        assert!(filter.check(&value).unwrap());
+        let value = json!({ "items": [{ "a<b": 1, "a": 0 }] });
+        let filter = Filter::new("items[a<b=1].a", Operator::Equals(json!(0)));
+        assert!(filter.check(&value).unwrap());
+
+        // Numbers compare by value, exactly, as in `Equals` and `GreaterThan`
+        let value = json!({ "xs": [
+            { "n": 3.0, "id": 1 },
+            { "n": 9007199254740993u64, "id": 2 },
+            { "n": 18446744073709551615u64, "id": 3 }
+        ] });
+        for (path, ids) in [
+            ("xs[?n=3].id", vec![1]),
+            (r#"xs[?"n"!=3].id"#, vec![2, 3]),
+            (r#"xs[?"n">9007199254740992].id"#, vec![2, 3]),
+            (r#"xs[?"n"<=9007199254740993].id"#, vec![1, 2]),
+            (r#"xs[?"n">=18446744073709551615].id"#, vec![3]),
+            (r#"xs[?"n"<3.5].id"#, vec![1]),
+        ] {
+            let filter = Filter::new(path, Operator::Exists(true));
+            let matched: Vec<Value> = filter
+                .resolve_branches(&value)
+                .unwrap()
+                .into_iter()
+                .map(|(_, id)| id.into_owned())
+                .collect();
+            assert_eq!(
+                matched,
+                ids.into_iter().map(Value::from).collect::<Vec<_>>(),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let sample = json!({
+            "age": 25,
+            "user": { "name": "John", "tags": ["a", "b"] }
+        });
+
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(20.0)),
+                Filter::new(
+                    "user",
+                    Operator::Or(vec![
+                        Filter::new("name", Operator::StartsWith("J".to_string())),
+                        Filter::new("nmae", Operator::EndsWith("n".to_string())),
+                        Filter::new("tags[0]", Operator::Equals(json!("a"))),
+                    ]),
+                ),
+            ]),
+        );
+
+        let issues = filter.dry_run(&sample);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].path, "user.nmae");
+        assert!(matches!(issues[0].error, FilterError::PathNotFound(..)));
+
+        // Operators are not evaluated, so a type mismatch isn't an issue
+        let filter = Filter::new("user.name", Operator::GreaterThan(1.0));
+        assert!(filter.dry_run(&sample).is_empty());
+    }
+
+    #[test]
+    fn test_coercions() {
+        let value = json!({ "age": "42", "active": 1, "name": "John", "score": "high" });
+        let options = FilterOptions {
+            coercions: HashMap::from([
+                ("age".to_string(), CoercionKind::Number),
+                ("active".to_string(), CoercionKind::Bool),
+                ("score".to_string(), CoercionKind::Number),
+            ]),
+            ..FilterOptions::default()
+        };
+
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(40.0)),
+                Filter::new("active", Operator::Equals(json!(true))),
+                Filter::new("name", Operator::StartsWith("J".to_string())),
+            ]),
+        );
+        assert!(filter.check_with_options(&value, &options).unwrap());
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+
+        // Paths without a rule stay strict, and unconvertible values pass through
+        let filter = Filter::new("name", Operator::GreaterThan(1.0));
+        assert!(filter.check_with_options(&value, &options).is_err());
+        let filter = Filter::new("score", Operator::GreaterThan(1.0));
+        assert!(matches!(
+            filter.check_with_options(&value, &options),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+
+        // Rules are keyed by the full path, with parent paths joined on
+        let value = json!({ "user": { "age": "42" }, "age": "42" });
+        let nested = Filter::new(
+            "user",
+            Operator::And(vec![Filter::new("age", Operator::GreaterThan(40.0))]),
+        );
+        assert!(matches!(
+            nested.check_with_options(&value, &options),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+        let options = FilterOptions {
+            coercions: HashMap::from([("user.age".to_string(), CoercionKind::Number)]),
+            ..FilterOptions::default()
+        };
+        assert!(nested.check_with_options(&value, &options).unwrap());
+        assert!(Filter::new("user.age", Operator::GreaterThan(40.0))
+            .check_with_options(&value, &options)
+            .unwrap());
+        assert!(Filter::new("age", Operator::GreaterThan(40.0))
+            .check_with_options(&value, &options)
+            .is_err());
+    }
+
+    #[test]
+    fn test_bools_as_numbers() {
+        let value = json!({ "active": 1, "deleted": 0.0, "admin": true, "count": 2 });
+        let options = FilterOptions {
+            bools_as_numbers: true,
+            ..FilterOptions::default()
+        };
+        let check = |path: &str, operator: Operator| {
+            let filter = Filter::new(path, operator);
+            (
+                filter.check(&value).unwrap(),
+                filter.check_with_options(&value, &options).unwrap(),
+            )
+        };
+
+        // (strict, widened)
+        assert_eq!(
+            check("active", Operator::Equals(json!(true))),
+            (false, true)
+        );
+        assert_eq!(
+            check("deleted", Operator::Equals(json!(false))),
+            (false, true)
+        );
+        assert_eq!(check("admin", Operator::Equals(json!(1.0))), (false, true));
+        assert_eq!(
+            check("active", Operator::NotEqual(json!(true))),
+            (true, false)
+        );
+        assert_eq!(
+            check("deleted", Operator::In(vec![json!(false)])),
+            (false, true)
+        );
+        assert_eq!(
+            check("active", Operator::Equals(json!(false))),
+            (false, false)
+        );
+        assert_eq!(
+            check("count", Operator::Equals(json!(true))),
+            (false, false)
+        );
+        assert_eq!(check("admin", Operator::Equals(json!(true))), (true, true));
+    }
+
+    #[test]
+    fn test_missing_containers_empty() {
+        let value = json!({ "user": {} });
+        let options = FilterOptions {
+            missing_containers_empty: true,
+            ..FilterOptions::default()
+        };
+        let check = |operator: Operator| {
+            Filter::new("user.tags", operator).check_with_options(&value, &options)
+        };
+
+        assert!(!check(Operator::ArrayContains(json!("admin"))).unwrap());
+        assert!(check(Operator::IsSubsetOf(vec![json!("admin")])).unwrap());
+        let is_empty = Operator::Length(Box::new(Operator::Equals(json!(0))));
+        assert!(check(is_empty.clone()).unwrap());
+        assert!(!check(Operator::HasKey("id".to_string())).unwrap());
+        assert!(check(Operator::OrFalseOnMismatch(Box::new(is_empty.clone()))).unwrap());
+
+        // Other operators still need the path
+        assert!(matches!(
+            check(Operator::Equals(json!([]))),
+            Err(FilterError::PathNotFound(..))
+        ));
+        assert!(matches!(
+            Filter::new("user.tags", is_empty).check(&value),
+            Err(FilterError::PathNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_and_or_short_circuit() {
+        let value = json!({ "age": 25, "name": "John" });
+        let yes = || Filter::new("age", Operator::GreaterThan(20.0));
+        let no = || Filter::new("age", Operator::LessThan(20.0));
+        let broken = || Filter::new("missing", Operator::Equals(json!(1)));
+        let and = |filters| Filter::new(".", Operator::And(filters));
+        let or = |filters| Filter::new(".", Operator::Or(filters));
+
+        // A decided result skips the remaining filters, errors included
+        for (filter, expected) in [
+            (and(vec![no(), broken()]), false),
+            (or(vec![yes(), broken()]), true),
+            (
+                and(vec![yes(), or(vec![yes(), broken()]), no(), broken()]),
+                false,
+            ),
+        ] {
+            assert_eq!(filter.check(&value).unwrap(), expected);
+            assert_eq!(filter.compile().check(&value).unwrap(), expected);
+        }
+
+        // Until then they're evaluated in order, so errors still propagate
+        for filter in [and(vec![yes(), broken()]), or(vec![no(), broken(), yes()])] {
+            assert!(matches!(
+                filter.check(&value),
+                Err(FilterError::PathNotFound(..))
+            ));
+            assert!(matches!(
+                filter.compile().check(&value),
+                Err(FilterError::PathNotFound(..))
+            ));
+        }
+
+        // Logical operators nested in other operators short-circuit too
+        let fields = |operator| {
+            Filter::new(
+                ".",
+                Operator::Fields {
+                    fields: BTreeMap::from([("name".to_string(), operator)]),
+                    missing_is_error: false,
+                },
+            )
+        };
+        let starts_with = |s: &str| Filter::new(".", Operator::StartsWith(s.to_string()));
+        let broken = || Filter::new(".", Operator::GreaterThan(1.0));
+        assert!(!fields(Operator::And(vec![starts_with("X"), broken()]))
+            .check(&value)
+            .unwrap());
+        assert!(fields(Operator::Or(vec![starts_with("J"), broken()]))
+            .check(&value)
+            .unwrap());
+        assert!(matches!(
+            fields(Operator::Or(vec![starts_with("X"), broken()])).check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_observed() {
+        let value = json!({ "age": 25, "user": { "name": "John" } });
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(20.0)),
+                Filter::new(
+                    "user",
+                    Operator::Or(vec![
+                        Filter::new("name", Operator::StartsWith("X".to_string())),
+                        Filter::new("email", Operator::EndsWith(".com".to_string())),
+                    ]),
+                ),
+            ]),
+        );
+
+        let mut seen = Vec::new();
+        let result = filter.check_observed(&value, |path, resolved| {
+            seen.push((path.to_string(), resolved.ok().cloned()));
+        });
+        assert!(matches!(result, Err(FilterError::PathNotFound(..))));
+        assert_eq!(
+            seen,
+            vec![
+                ("age".to_string(), Some(json!(25))),
+                ("user.name".to_string(), Some(json!("John"))),
+                ("user.email".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_against_file() {
+        let dir = std::env::temp_dir();
+        let write = |name: &str, contents: &str| {
+            let path = dir.join(format!("json-filter-{}-{}", std::process::id(), name));
+            std::fs::write(&path, contents).unwrap();
+            path
+        };
+        let filter = Filter::new("age", Operator::GreaterThan(20.0));
+
+        let matching = write("matching.json", r#"{ "age": 25 }"#);
+        let other = write("other.json", r#"{ "age": 15 }"#);
+        let invalid = write("invalid.json", r#"{ "age": "#);
+        let wrong_type = write("wrong_type.json", r#"{ "age": "25" }"#);
+
+        assert!(filter.check_against_file(&matching).unwrap());
+        assert!(!filter.check_against_file(&other).unwrap());
+        assert!(matches!(
+            filter.check_against_file(&invalid),
+            Err(CheckError::Parse(..))
+        ));
+        assert!(matches!(
+            filter.check_against_file(&wrong_type),
+            Err(CheckError::Filter(FilterError::TypeMismatch { .. }))
+        ));
+        assert!(matches!(
+            filter.check_against_file(&dir.join("json-filter-does-not-exist.json")),
+            Err(CheckError::Io(..))
+        ));
 
-        let filter = Filter::new("age", Operator::GreaterOrEqual(25.0));
-        assert!(filter.check(&value).unwrap());
+        for path in [matching, other, invalid, wrong_type] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
 
-        let filter = Filter::new("age", Operator::LessOrEqual(25.0));
-        assert!(filter.check(&value).unwrap());
+    #[test]
+    fn test_check_with_deadline() {
+        let value = json!({ "age": 25, "name": "John" });
+        let filter = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(20.0)),
+                Filter::new("name", Operator::StartsWith("J".to_string())),
+            ]),
+        );
+
+        let deadline = Instant::now() + std::time::Duration::from_secs(60);
+        assert!(filter.check_with_deadline(&value, deadline).unwrap());
+
+        assert!(matches!(
+            filter.check_with_deadline(&value, Instant::now()),
+            Err(FilterError::Timeout)
+        ));
     }
 
+    #[cfg(feature = "regex")]
     #[test]
-    fn test_string_operators() {
-        let value = json!({ "name": "John Doe" });
+    fn test_check_with_deadline_slow_regex() {
+        // Every clause recompiles a Unicode-aware regex, which adds up quickly
+        let value = json!({ "text": "order-12345" });
+        let slow = Filter::new(
+            "text",
+            Operator::RegexCapture {
+                pattern: r"^(\w+)-(\d+)$".to_string(),
+                group: CaptureGroup::Index(1),
+                inner: Box::new(Operator::Equals(json!("a"))),
+            },
+        );
+        let filter = Filter::new(".", Operator::Or(vec![slow; 10_000]));
 
-        let filter = Filter::new("name", Operator::StartsWith("John".to_string()));
-        assert!(filter.check(&value).unwrap());
+        let deadline = Instant::now() + std::time::Duration::from_millis(1);
+        assert!(matches!(
+            filter.check_with_deadline(&value, deadline),
+            Err(FilterError::Timeout)
+        ));
+    }
 
-        let filter = Filter::new("name", Operator::EndsWith("Doe".to_string()));
-        assert!(filter.check(&value).unwrap());
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_capture() {
+        let value = json!({ "version": "v12.4.1", "id": 7 });
+        let capture = |group: usize, inner: Operator| {
+            Filter::new(
+                "version",
+                Operator::RegexCapture {
+                    pattern: r"^v(\d+)\.(\d+)".to_string(),
+                    group: CaptureGroup::Index(group),
+                    inner: Box::new(inner),
+                },
+            )
+        };
 
-        let filter = Filter::new("name", Operator::Contains("hn D".to_string()));
+        assert!(capture(1, Operator::Equals(json!("12")))
+            .check(&value)
+            .unwrap());
+        assert!(capture(2, Operator::EndsWith("4".to_string()))
+            .check(&value)
+            .unwrap());
+        assert!(!capture(1, Operator::Equals(json!("4")))
+            .check(&value)
+            .unwrap());
+
+        let filter = Filter::new(
+            "version",
+            Operator::RegexCapture {
+                pattern: r"^release-(\d+)".to_string(),
+                group: CaptureGroup::Index(1),
+                inner: Box::new(Operator::Equals(json!("12"))),
+            },
+        );
+        assert!(!filter.check(&value).unwrap());
+
+        assert!(matches!(
+            capture(3, Operator::Equals(json!("1"))).check(&value),
+            Err(FilterError::InvalidRegex(..))
+        ));
+        assert!(matches!(
+            Filter::new("id", capture(1, Operator::Equals(json!("1"))).operator).check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+
+        let named = |group: &str| {
+            Filter::new(
+                "version",
+                Operator::RegexCapture {
+                    pattern: r"^v(?P<major>\d+)\.(?P<minor>\d+)".to_string(),
+                    group: CaptureGroup::Name(group.to_string()),
+                    inner: Box::new(Operator::Equals(json!("4"))),
+                },
+            )
+        };
+        assert!(named("minor").check(&value).unwrap());
+        assert!(!named("major").check(&value).unwrap());
+        assert!(matches!(
+            named("patch").validate(),
+            Err(FilterError::InvalidRegex(..))
+        ));
+        assert!(matches!(
+            capture(3, Operator::Equals(json!("1"))).validate(),
+            Err(FilterError::InvalidRegex(..))
+        ));
+        let filter: Filter = serde_json::from_value(json!({
+            "path": "version",
+            "operator": { "RegexCapture": {
+                "pattern": r"^v(?P<major>\d+)",
+                "group": "major",
+                "inner": { "Equals": "12" }
+            } }
+        }))
+        .unwrap();
         assert!(filter.check(&value).unwrap());
     }
 
+    #[cfg(feature = "regex")]
     #[test]
-    fn test_array_operators() {
-        let value = json!({ "tags": ["rust", "coding", "json"] });
+    fn test_regex() {
+        let value = json!({ "email": "john@example.com", "bad": "john@", "n": 1 });
+        let email = |path: &str| {
+            Filter::new(
+                path,
+                Operator::Regex(r"^[^@\s]+@[^@\s]+\.[a-z]+$".to_string()),
+            )
+        };
 
-        let filter = Filter::new("tags", Operator::ArrayContains(json!("rust")));
-        assert!(filter.check(&value).unwrap());
+        assert!(email("email").check(&value).unwrap());
+        assert!(!email("bad").check(&value).unwrap());
+        assert!(email("email").compile().check(&value).unwrap());
+        assert!(!email("bad").compile().check(&value).unwrap());
+        assert!(matches!(
+            email("n").check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
 
-        let filter = Filter::new("tags[1]", Operator::Equals(json!("coding")));
+        let filter = Filter::new("email", Operator::Regex("(".to_string()));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::InvalidRegex(..))
+        ));
+        assert!(matches!(
+            filter.compile().check(&value),
+            Err(FilterError::InvalidRegex(..))
+        ));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_try_new_regex() {
+        assert!(Filter::try_new("email", Operator::Regex("^.+@.+$".to_string())).is_ok());
+        assert!(matches!(
+            Filter::try_new("email", Operator::Regex("(".to_string())),
+            Err(FilterError::InvalidRegex(..))
+        ));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_field() {
+        let sku = regex::Regex::new(r"^[A-Z]{3}-\d{4}$").unwrap();
+        let value = json!({ "sku": "ABC-1234", "parent": "abc-1", "count": 3 });
+
+        let filter = Filter::regex_field("sku", sku.clone());
+        let parent = Filter::regex_field("parent", sku.clone());
         assert!(filter.check(&value).unwrap());
+        assert!(!parent.check(&value).unwrap());
+        assert!(matches!(
+            Filter::regex_field("count", sku.clone()).check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+
+        assert_eq!(filter, Filter::regex_field("sku", sku));
+        assert_eq!(filter.hash_key(), filter.clone().hash_key());
+        assert_ne!(
+            filter.hash_key(),
+            Filter::regex_field("sku", regex::Regex::new("^x$").unwrap()).hash_key()
+        );
+        assert!(serde_json::to_string(&filter).is_err());
     }
 
+    #[cfg(feature = "diacritics")]
     #[test]
-    fn test_object_operators() {
+    fn test_contains_normalized() {
+        let value = json!({ "accented": "Le Café Crème", "plain": "Le Cafe Creme", "n": 1 });
+        let contains = |path: &str, s: &str| {
+            Filter::new(path, Operator::ContainsNormalized(s.to_string())).check(&value)
+        };
+
+        assert!(contains("accented", "Cafe").unwrap());
+        assert!(contains("accented", "Café Crème").unwrap());
+        assert!(contains("plain", "Café").unwrap());
+        assert!(contains("plain", "Crème").unwrap());
+        // Case still matters
+        assert!(!contains("plain", "café").unwrap());
+        assert!(!contains("accented", "thé").unwrap());
+        assert!(matches!(
+            contains("n", "1"),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[cfg(feature = "collation")]
+    #[test]
+    fn test_collated_equals() {
+        let value = json!({ "name": "Äpfel", "surname": "Müller", "age": 25 });
+        let collated = |target: &str, locale: &str| Operator::CollatedEquals {
+            target: target.to_string(),
+            locale: locale.to_string(),
+        };
+        let check = |path: &str, operator| Filter::new(path, operator).check(&value);
+
+        // German treats `Ä` as an accented `A`, Swedish as a separate letter
+        assert!(check("name", collated("apfel", "de")).unwrap());
+        assert!(!check("name", collated("apfel", "sv")).unwrap());
+        assert!(!check("name", Operator::Equals(json!("apfel"))).unwrap());
+
+        // The German phonebook ordering spells umlauts out
+        assert!(check("surname", collated("Mueller", "de-u-co-phonebk")).unwrap());
+        assert!(!check("surname", collated("Mueller", "de")).unwrap());
+
+        // Languages without their own rules use the root collation
+        assert!(check("name", collated("apfel", "en")).unwrap());
+        assert!(check("name", collated("apfel", "en-GB")).unwrap());
+
+        for locale in ["not a locale", "xx-YY", "qq"] {
+            assert!(
+                matches!(
+                    check("name", collated("apfel", locale)),
+                    Err(FilterError::InvalidLocale(..))
+                ),
+                "{}",
+                locale
+            );
+        }
+        assert!(matches!(
+            check("age", collated("25", "de")),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn test_hash_equals() {
+        let value = json!({ "password": "hello", "age": 25 });
+        let hash_equals = |algorithm, expected: &str| Operator::HashEquals {
+            algorithm,
+            expected: expected.to_string(),
+        };
+        let sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let sha512 = "9b71d224bd62f3785d96d46ad3ea3d73319bfbc2890caadae2dff72519673ca7\
+                      2323c3d99ba5c11d7c7acc6e14b8c5da0c4663475c2e5c3adef46f73bcdec043";
+
+        let check = |path: &str, operator| Filter::new(path, operator).check(&value);
+        assert!(check("password", hash_equals(HashAlgo::Sha256, sha256)).unwrap());
+        assert!(check("password", hash_equals(HashAlgo::Sha512, sha512)).unwrap());
+        assert!(check(
+            "password",
+            hash_equals(HashAlgo::Sha256, &sha256.to_uppercase())
+        )
+        .unwrap());
+        let other = "486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7";
+        assert!(!check("password", hash_equals(HashAlgo::Sha256, other)).unwrap());
+        assert!(matches!(
+            check("age", hash_equals(HashAlgo::Sha256, sha256)),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+
+        assert!(matches!(
+            Filter::try_new("password", hash_equals(HashAlgo::Sha512, sha256)),
+            Err(FilterError::InvalidOperand(..))
+        ));
+        assert!(matches!(
+            Filter::try_new("password", hash_equals(HashAlgo::Sha256, &"g".repeat(64))),
+            Err(FilterError::InvalidOperand(..))
+        ));
+    }
+
+    #[cfg(feature = "phonetic")]
+    #[test]
+    fn test_phonetic() {
+        assert_eq!(soundex("Robert"), "R163");
+        assert_eq!(soundex("Rupert"), "R163");
+        assert_eq!(soundex("Ashcraft"), "A261");
+        assert_eq!(soundex("Tymczak"), "T522");
+        assert_eq!(soundex("Pfister"), "P236");
+        assert_eq!(soundex("Lee"), "L000");
+
+        let value = json!({ "name": "Smyth", "other": "Jones", "age": 25 });
+        let soundex_of = |target: &str| Operator::Phonetic {
+            target: target.to_string(),
+            algorithm: PhoneticAlgo::Soundex,
+        };
+
+        assert!(Filter::new("name", soundex_of("Smith"))
+            .check(&value)
+            .unwrap());
+        assert!(!Filter::new("other", soundex_of("Smith"))
+            .check(&value)
+            .unwrap());
+        assert!(matches!(
+            Filter::new("age", soundex_of("Smith")).check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_all_elements_segment() {
         let value = json!({
-            "user": {
-                "id": 123,
-                "details": {
-                    "email": "john@example.com"
-                }
-            }
+            "items": [{ "price": 5 }, { "price": 25 }, { "price": 12 }],
+            "orders": [{ "lines": [{ "qty": 1 }] }, { "lines": [{ "qty": 3 }, { "qty": 7 }] }],
+            "meta": { "price": 100 },
         });
 
-        let filter = Filter::new("user", Operator::HasKey("id".to_string()));
+        let filter = Filter::new("items[].price", Operator::GreaterThan(20.0));
         assert!(filter.check(&value).unwrap());
+        assert!(!filter.check_quantified(&value, Quantifier::All).unwrap());
+        assert_eq!(
+            filter.matching_paths(&value).unwrap(),
+            vec!["items[1].price".to_string()]
+        );
 
-        let filter = Filter::new(
-            "user.details.email",
-            Operator::EndsWith("@example.com".to_string()),
+        let filter = Filter::new("orders[].lines[].qty", Operator::GreaterThan(5.0));
+        assert_eq!(
+            filter.matching_paths(&value).unwrap(),
+            vec!["orders[1].lines[1].qty".to_string()]
         );
+
+        // Unlike `*`, `[]` only applies to arrays
+        let filter = Filter::new("meta[]", Operator::GreaterThan(20.0));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_wildcard() {
+        let value = json!({
+            "items": [{ "price": 10 }, { "price": 20 }],
+            "prices": { "small": 5, "large": 50 },
+            "empty": [],
+            "none": {},
+            "label": "x",
+        });
+
+        let filter = Filter::new("items.*.price", Operator::GreaterThan(15.0));
         assert!(filter.check(&value).unwrap());
+        let filter = Filter::new("items.*.price", Operator::GreaterThan(25.0));
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new("prices.*", Operator::LessThan(10.0));
+        assert_eq!(
+            filter.matching_paths(&value).unwrap(),
+            vec!["prices.small".to_string()]
+        );
+
+        // Selecting nothing is a non-match, not an error
+        let filter = Filter::new("empty.*.price", Operator::GreaterThan(0.0));
+        assert!(!filter.check(&value).unwrap());
+        let filter = Filter::new("none.*", Operator::GreaterThan(0.0));
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new("label.*", Operator::GreaterThan(0.0));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
     }
 
     #[test]
-    fn test_logical_operators() {
+    fn test_quoted_keys() {
         let value = json!({
-            "age": 25,
-            "name": "John Doe"
+            "a.b": { "c": 1 },
+            "a": { "b": { "c": 2 } },
+            "config": { "server.port": 8080, "tags[0]": "literal", "tags": ["x"] }
+        });
+        let check =
+            |path: &str, target: Value| Filter::new(path, Operator::Equals(target)).check(&value);
+
+        assert!(check(r#"["a.b"]"#, json!({ "c": 1 })).unwrap());
+        assert!(check(r#"["a.b"].c"#, json!(1)).unwrap());
+        assert!(check("a.b.c", json!(2)).unwrap());
+        assert!(check(r#"config["server.port"]"#, json!(8080)).unwrap());
+        assert!(check(r#"config.["server.port"]"#, json!(8080)).unwrap());
+        assert!(check(r#"config["tags[0]"]"#, json!("literal")).unwrap());
+        assert!(check("config.tags[0]", json!("x")).unwrap());
+
+        // Concrete paths quote such keys so they can be resolved again
+        let filter = Filter::new("config.*", Operator::Equals(json!(8080)));
+        let paths = filter.matching_paths(&value).unwrap();
+        assert_eq!(paths, vec![r#"config["server.port"]"#.to_string()]);
+        assert!(check(&paths[0], json!(8080)).unwrap());
+    }
+
+    #[test]
+    fn test_root_index() {
+        let value = json!([{ "id": 1 }, { "id": 2, "tags": ["a"] }]);
+
+        assert!(Filter::new("[0].id", Operator::Equals(json!(1)))
+            .check(&value)
+            .unwrap());
+        assert!(Filter::new("[-1].tags[0]", Operator::Equals(json!("a")))
+            .check(&value)
+            .unwrap());
+        assert!(Filter::new("[1]", Operator::HasKey("tags".to_string()))
+            .check(&value)
+            .unwrap());
+        assert!(matches!(
+            Filter::new("[2].id", Operator::Equals(json!(1))).check(&value),
+            Err(FilterError::InvalidArrayIndex(..))
+        ));
+        assert!(matches!(
+            Filter::new("[0].id", Operator::Equals(json!(1))).check(&json!({ "id": 1 })),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_negative_index() {
+        let value = json!({ "items": ["a", "b", "c"] });
+        let filter = |path: &str, target: &str| Filter::new(path, Operator::Equals(json!(target)));
+
+        assert!(filter("items[-1]", "c").check(&value).unwrap());
+        assert!(filter("items[-2]", "b").check(&value).unwrap());
+        assert!(filter("items[-3]", "a").check(&value).unwrap());
+        assert_eq!(
+            filter("items[-1]", "c").matching_paths(&value).unwrap(),
+            vec!["items[2]".to_string()]
+        );
+
+        assert!(matches!(
+            filter("items[-4]", "a").check(&value),
+            Err(FilterError::InvalidArrayIndex(index)) if index == "-4"
+        ));
+        assert!(matches!(
+            filter("items[-1]", "a").check(&json!({ "items": [] })),
+            Err(FilterError::InvalidArrayIndex(..))
+        ));
+    }
+
+    #[test]
+    fn test_chained_indices() {
+        let value = json!({
+            "matrix": [[1, 2], [3, 4]],
+            "cube": [[[1, 2], [3, 4]], [[5, 6], [7, 8]]]
+        });
+        let filter = |path: &str, target: i64| Filter::new(path, Operator::Equals(json!(target)));
+
+        assert!(filter("matrix[0][1]", 2).check(&value).unwrap());
+        assert!(filter("matrix[1][0]", 3).check(&value).unwrap());
+        assert!(filter("matrix[-1][-1]", 4).check(&value).unwrap());
+        assert!(filter("cube[1][0][1]", 6).check(&value).unwrap());
+        assert!(!filter("cube[0][1][0]", 4).check(&value).unwrap());
+        assert_eq!(
+            filter("cube[1][-1][0]", 7).matching_paths(&value).unwrap(),
+            vec!["cube[1][1][0]".to_string()]
+        );
+
+        assert!(matches!(
+            filter("matrix[0][2]", 1).check(&value),
+            Err(FilterError::InvalidArrayIndex(index)) if index == "2"
+        ));
+        assert!(matches!(
+            filter("cube[1][1][5]", 1).check(&value),
+            Err(FilterError::InvalidArrayIndex(..))
+        ));
+    }
+
+    #[test]
+    fn test_wildcard_with_index() {
+        let value = json!({
+            "orders": [
+                { "items": [{ "sku": "A-1" }, { "sku": "B-2" }] },
+                { "items": [{ "sku": "C-3" }] },
+                { "items": [{ "sku": "D-4" }, { "sku": "A-5" }] }
+            ]
         });
 
         let filter = Filter::new(
-            ".",
-            Operator::And(vec![
-                Filter::new("age", Operator::GreaterThan(20.0)),
-                Filter::new("name", Operator::StartsWith("John".to_string())),
-            ]),
+            "orders.*.items[0].sku",
+            Operator::StartsWith("C".to_string()),
         );
         assert!(filter.check(&value).unwrap());
+        assert_eq!(
+            filter.matching_paths(&value).unwrap(),
+            vec!["orders[1].items[0].sku".to_string()]
+        );
+
+        // Only the first item of each order is considered
+        let filter = Filter::new("orders.*.items[0].sku", Operator::Equals(json!("A-5")));
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new("orders.*.items[0].sku", Operator::Contains("-".to_string()));
+        assert!(filter.check_quantified(&value, Quantifier::All).unwrap());
+
+        let filter = Filter::new("orders.*.items[1].sku", Operator::Contains("-".to_string()));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::InvalidArrayIndex(..))
+        ));
+    }
+
+    #[test]
+    fn test_keys_suffix() {
+        let value =
+            json!({ "obj": { "b": 2, "a": 1 }, "list": [1, 2], "items": [{ "x": 1 }, { "y": 2 }] });
 
         let filter = Filter::new(
-            ".",
-            Operator::Or(vec![
-                Filter::new("age", Operator::GreaterThan(30.0)),
-                Filter::new("name", Operator::Contains("John".to_string())),
-            ]),
+            "obj#keys",
+            Operator::DeepEquals {
+                target: json!(["a", "b"]),
+                ignore_array_order: true,
+            },
+        );
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("obj#keys", Operator::ArrayContains(json!("b")));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("items.*#keys", Operator::ArrayContains(json!("y")));
+        assert_eq!(
+            filter.matching_paths(&value).unwrap(),
+            vec!["items[1]#keys".to_string()]
         );
+
+        let filter = Filter::new("#keys", Operator::ArrayContains(json!("list")));
         assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("list#keys", Operator::ArrayContains(json!(0)));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::TypeMismatch { .. })
+        ));
+
+        let filter = Filter::new("obj#nope", Operator::ArrayContains(json!("a")));
+        assert!(matches!(
+            filter.check(&value),
+            Err(FilterError::InvalidPath(..))
+        ));
     }
 
     #[test]
-    fn test_type_mismatch() {
-        let value = json!({ "age": "25" }); // age is a string, not a number
+    fn test_length_and_sum_suffixes() {
+        let value = json!({
+            "name": "Zoë",
+            "tags": ["a", "b"],
+            "amounts": [1.5, 2, 3],
+            "mixed": [1, "2"],
+            "n": 5,
+        });
+        let check = |path: &str, operator: Operator| Filter::new(path, operator).check(&value);
 
-        let filter = Filter::new("age", Operator::GreaterThan(20.0));
+        assert!(check("name#length", Operator::GreaterThan(2.0)).unwrap());
+        assert!(check("name#length", Operator::Equals(json!(3))).unwrap());
+        assert!(check("tags#length", Operator::LessThan(3.0)).unwrap());
+        assert!(check("amounts#sum", Operator::LessThan(7.0)).unwrap());
+        assert!(!check("amounts#sum", Operator::LessThan(6.5)).unwrap());
+        assert!(check("#length", Operator::GreaterThan(0.0)).is_err());
+
+        for path in ["n#length", "n#sum", "mixed#sum"] {
+            assert!(matches!(
+                check(path, Operator::GreaterThan(0.0)),
+                Err(FilterError::TypeMismatch { .. })
+            ));
+        }
+    }
+
+    #[cfg(feature = "byte-size")]
+    #[test]
+    fn test_size_greater_than() {
+        let value = json!({ "size": "1GB", "small": "900MB", "binary": "1 GiB" });
+
+        let filter = Filter::new("size", Operator::SizeGreaterThan("900MB".to_string()));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("small", Operator::SizeGreaterThan("1GB".to_string()));
+        assert!(!filter.check(&value).unwrap());
+
+        let filter = Filter::new("binary", Operator::SizeGreaterThan("1GB".to_string()));
+        assert!(filter.check(&value).unwrap());
+
+        let filter = Filter::new("size", Operator::SizeGreaterThan("lots".to_string()));
         assert!(matches!(
             filter.check(&value),
+            Err(FilterError::InvalidByteSize(..))
+        ));
+    }
+
+    #[cfg(feature = "geo")]
+    #[test]
+    fn test_within_distance() {
+        let value = json!({
+            "eiffel_tower": { "lat": 48.8584, "lng": 2.2945 },
+            "big_ben": { "lat": 51.5007, "lng": -0.1246 },
+            "unknown": { "lat": "48.8584", "lng": 2.2945 }
+        });
+        // Within 10 km of central Paris
+        let check = |path: &str| {
+            Filter::new(
+                path,
+                Operator::WithinDistance {
+                    lat: 48.8566,
+                    lng: 2.3522,
+                    km: 10.0,
+                    lat_path: "lat".to_string(),
+                    lng_path: "lng".to_string(),
+                },
+            )
+            .check(&value)
+        };
+
+        assert!(check("eiffel_tower").unwrap());
+        assert!(!check("big_ben").unwrap());
+        assert!(matches!(
+            check("unknown"),
             Err(FilterError::TypeMismatch { .. })
         ));
+
+        // Coordinates of different points are never paired up
+        let value = json!({
+            "stores": [
+                { "lat": 48.8584, "lng": -0.1246 },
+                { "lat": 51.5007, "lng": 2.2945 }
+            ]
+        });
+        let near = |path: &str, lat_path: &str, lng_path: &str| {
+            Filter::new(
+                path,
+                Operator::WithinDistance {
+                    lat: 48.8566,
+                    lng: 2.3522,
+                    km: 10.0,
+                    lat_path: lat_path.to_string(),
+                    lng_path: lng_path.to_string(),
+                },
+            )
+        };
+        assert!(!near("stores[]", "lat", "lng").check(&value).unwrap());
+        let mut paris = value.clone();
+        paris["stores"][1]["lat"] = json!(48.8584);
+        assert!(near("stores[]", "lat", "lng").check(&paris).unwrap());
+        for filter in [
+            near(".", "stores[].lat", "stores[].lng"),
+            near(".", "stores[0].lat", "stores.*.lng"),
+        ] {
+            assert!(matches!(
+                filter.check(&value),
+                Err(FilterError::InvalidOperand(..))
+            ));
+            assert!(matches!(
+                filter.validate(),
+                Err(FilterError::InvalidOperand(..))
+            ));
+        }
+
+        // Paris to London is about 344 km
+        let distance = haversine_km(48.8566, 2.3522, 51.5074, -0.1278);
+        assert!((distance - 343.5).abs() < 1.0, "{}", distance);
     }
 
+    #[cfg(feature = "datetime")]
     #[test]
-    fn test_path_not_found() {
-        let value = json!({ "name": "John" });
+    fn test_within_duration_of() {
+        let value = json!({
+            "created_at": "2024-05-01T12:00:00Z",
+            "updated_at": "2024-05-01T14:04:59+02:00",
+            "deleted_at": "2024-05-01T12:05:00.001Z",
+            "bad": "yesterday",
+        });
+        let within = |path: &str, other_path: &str| {
+            Filter::new(
+                path,
+                Operator::WithinDurationOf {
+                    other_path: other_path.to_string(),
+                    duration_secs: 300,
+                },
+            )
+            .check(&value)
+        };
 
-        let filter = Filter::new("age", Operator::GreaterThan(20.0));
+        assert!(within("updated_at", "created_at").unwrap());
+        assert!(within("created_at", "updated_at").unwrap());
+        assert!(!within("deleted_at", "created_at").unwrap());
         assert!(matches!(
-            filter.check(&value),
-            Err(FilterError::PathNotFound(..))
+            within("bad", "created_at"),
+            Err(FilterError::InvalidTimestamp(..))
+        ));
+        assert!(matches!(
+            within("created_at", "bad"),
+            Err(FilterError::InvalidTimestamp(..))
         ));
     }
 }
@@ -0,0 +1,192 @@
+//! The [`filter!`](crate::filter) macro.
+
+/// Builds a [`Filter`](crate::Filter) from a condition written like Rust code.
+///
+/// Paths are dotted identifiers (`user.details.email`) or string literals for
+/// anything else (`"tags[1]"`). Each comparison is one of `>`, `<`, `>=`, `<=`
+/// (numbers), `==`, `!=` (any value convertible into a JSON `Value`) or
+/// `starts_with`, `ends_with`, `contains` (strings). Comparisons combine with
+/// `&&`, which binds tighter than `||`, and group with parentheses, which `!`
+/// negates.
+///
+/// ```
+/// use json_filter::{filter, Filter, Operator};
+/// use serde_json::json;
+///
+/// let filter = filter!(age > 20 && (name starts_with "John" || "tags[0]" == "vip"));
+/// assert_eq!(
+///     filter,
+///     Filter::new(
+///         ".",
+///         Operator::And(vec![
+///             Filter::new("age", Operator::GreaterThan(20.0)),
+///             Filter::new(
+///                 ".",
+///                 Operator::Or(vec![
+///                     Filter::new("name", Operator::StartsWith("John".to_string())),
+///                     Filter::new("tags[0]", Operator::Equals(json!("vip"))),
+///                 ]),
+///             ),
+///         ]),
+///     )
+/// );
+/// assert!(filter.check(&json!({ "age": 25, "name": "John Doe", "tags": ["new"] })).unwrap());
+/// ```
+#[macro_export]
+macro_rules! filter {
+    // Splits on `||`, then each side on `&&`
+    (@or [$($groups:tt)*] [$($current:tt)+] || $($rest:tt)+) => {
+        $crate::filter!(@or [$($groups)* [$($current)+]] [] $($rest)+)
+    };
+    (@or [$($groups:tt)*] [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::filter!(@or [$($groups)*] [$($current)* $next] $($rest)*)
+    };
+    (@or [] [$($current:tt)+]) => {
+        $crate::filter!(@and [] [] $($current)+)
+    };
+    (@or [$([$($group:tt)+])+] [$($current:tt)+]) => {
+        $crate::Filter::new(
+            ".",
+            $crate::Operator::Or(vec![
+                $($crate::filter!(@and [] [] $($group)+),)+
+                $crate::filter!(@and [] [] $($current)+),
+            ]),
+        )
+    };
+
+    (@and [$($atoms:tt)*] [$($current:tt)+] && $($rest:tt)+) => {
+        $crate::filter!(@and [$($atoms)* [$($current)+]] [] $($rest)+)
+    };
+    (@and [$($atoms:tt)*] [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::filter!(@and [$($atoms)*] [$($current)* $next] $($rest)*)
+    };
+    (@and [] [$($current:tt)+]) => {
+        $crate::filter!(@atom $($current)+)
+    };
+    (@and [$([$($atom:tt)+])+] [$($current:tt)+]) => {
+        $crate::Filter::new(
+            ".",
+            $crate::Operator::And(vec![
+                $($crate::filter!(@atom $($atom)+),)+
+                $crate::filter!(@atom $($current)+),
+            ]),
+        )
+    };
+
+    (@atom ($($inner:tt)+)) => {
+        $crate::filter!($($inner)+)
+    };
+    (@atom ! ($($inner:tt)+)) => {
+        $crate::Filter::new(
+            ".",
+            $crate::Operator::Not(::std::boxed::Box::new($crate::filter!($($inner)+))),
+        )
+    };
+    (@atom $path:literal $($rest:tt)+) => {
+        $crate::filter!(@op ($path) $($rest)+)
+    };
+    (@atom $($rest:tt)+) => {
+        $crate::filter!(@path [] $($rest)+)
+    };
+
+    // Collects dotted identifiers into a path literal
+    (@path [$($segments:tt)*] $segment:ident . $($rest:tt)+) => {
+        $crate::filter!(@path [$($segments)* $segment] $($rest)+)
+    };
+    (@path [$($segments:ident)*] $segment:ident $($rest:tt)+) => {
+        $crate::filter!(
+            @op (concat!($(stringify!($segments), ".",)* stringify!($segment)))
+            $($rest)+
+        )
+    };
+
+    (@op ($path:expr) > $value:expr) => {
+        $crate::Filter::new($path, $crate::Operator::GreaterThan($crate::filter!(@f64 $value)))
+    };
+    (@op ($path:expr) < $value:expr) => {
+        $crate::Filter::new($path, $crate::Operator::LessThan($crate::filter!(@f64 $value)))
+    };
+    (@op ($path:expr) >= $value:expr) => {
+        $crate::Filter::new($path, $crate::Operator::GreaterOrEqual($crate::filter!(@f64 $value)))
+    };
+    (@op ($path:expr) <= $value:expr) => {
+        $crate::Filter::new($path, $crate::Operator::LessOrEqual($crate::filter!(@f64 $value)))
+    };
+    (@op ($path:expr) == $value:expr) => {
+        $crate::Filter::new($path, $crate::Operator::Equals($crate::__serde_json::Value::from($value)))
+    };
+    (@op ($path:expr) != $value:expr) => {
+        $crate::Filter::new($path, $crate::Operator::NotEqual($crate::__serde_json::Value::from($value)))
+    };
+    (@op ($path:expr) starts_with $value:expr) => {
+        $crate::Filter::new($path, $crate::Operator::StartsWith(::std::string::ToString::to_string(&$value)))
+    };
+    (@op ($path:expr) ends_with $value:expr) => {
+        $crate::Filter::new($path, $crate::Operator::EndsWith(::std::string::ToString::to_string(&$value)))
+    };
+    (@op ($path:expr) contains $value:expr) => {
+        $crate::Filter::new($path, $crate::Operator::Contains(::std::string::ToString::to_string(&$value)))
+    };
+
+    (@f64 $value:expr) => {{
+        #[allow(clippy::unnecessary_cast)]
+        let value = $value as f64;
+        value
+    }};
+
+    ($($condition:tt)+) => {
+        $crate::filter!(@or [] [] $($condition)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Filter, Operator};
+    use serde_json::json;
+
+    #[test]
+    fn test_filter_macro() {
+        assert_eq!(
+            filter!(age >= 18),
+            Filter::new("age", Operator::GreaterOrEqual(18.0))
+        );
+        assert_eq!(
+            filter!(user.details.email ends_with "@example.com"),
+            Filter::new(
+                "user.details.email",
+                Operator::EndsWith("@example.com".to_string())
+            )
+        );
+
+        let min_age = 18;
+        let expected = Filter::new(
+            ".",
+            Operator::Or(vec![
+                Filter::new(
+                    ".",
+                    Operator::And(vec![
+                        Filter::new("age", Operator::GreaterThan(18.0)),
+                        Filter::new("age", Operator::LessThan(65.5)),
+                    ]),
+                ),
+                Filter::new("role", Operator::Equals(json!("admin"))),
+                Filter::new(
+                    ".",
+                    Operator::Not(Box::new(Filter::new(
+                        "tags[0]",
+                        Operator::NotEqual(json!(true)),
+                    ))),
+                ),
+            ]),
+        );
+        assert_eq!(
+            filter!(age > min_age && age < 65.5 || role == "admin" || !("tags[0]" != true)),
+            expected
+        );
+
+        let filter = filter!(name contains "oh" && (age <= 30 || user.id == 1));
+        assert!(filter
+            .check(&json!({ "name": "John", "age": 40, "user": { "id": 1 } }))
+            .unwrap());
+    }
+}
@@ -0,0 +1,286 @@
+//! Conversion between filters and a subset of MongoDB query documents.
+//!
+//! Supported: implicit equality, `$eq`, `$ne`, `$gt`, `$gte`, `$lt`, `$lte`,
+//! `$in`, `$and` and `$or`. Anything else is reported as
+//! [`FilterError::Unsupported`].
+
+use serde_json::{Map, Value};
+
+use crate::{nested_path, Filter, FilterError, Operator};
+
+impl Filter {
+    /// Parses a MongoDB-style query document such as
+    /// `{ "age": { "$gt": 20 }, "status": { "$in": ["a", "b"] } }`.
+    ///
+    /// Several conditions in one document are combined with `And`.
+    pub fn from_mongo_query(query: &Value) -> Result<Filter, FilterError> {
+        let Value::Object(query) = query else {
+            return Err(FilterError::Unsupported(format!(
+                "query must be an object, got {:?}",
+                query
+            )));
+        };
+
+        let mut filters = Vec::with_capacity(query.len());
+        for (key, condition) in query {
+            let filter = match key.as_str() {
+                "$and" => Filter::new(".", Operator::And(parse_query_list(key, condition)?)),
+                "$or" => Filter::new(".", Operator::Or(parse_query_list(key, condition)?)),
+                key if key.starts_with('$') => {
+                    return Err(FilterError::Unsupported(key.to_string()))
+                }
+                field => parse_condition(field, condition)?,
+            };
+            filters.push(filter);
+        }
+
+        Ok(single_or_and(".", filters))
+    }
+
+    /// Converts this filter into a MongoDB-style query document, the inverse of
+    /// [`Filter::from_mongo_query`]. Operators outside the supported subset are
+    /// reported as [`FilterError::Unsupported`].
+    pub fn to_mongo_query(&self) -> Result<Value, FilterError> {
+        to_mongo(self, ".")
+    }
+}
+
+fn parse_query_list(key: &str, condition: &Value) -> Result<Vec<Filter>, FilterError> {
+    match condition {
+        Value::Array(queries) => queries.iter().map(Filter::from_mongo_query).collect(),
+        _ => Err(FilterError::Unsupported(format!(
+            "{} expects an array, got {:?}",
+            key, condition
+        ))),
+    }
+}
+
+fn parse_condition(field: &str, condition: &Value) -> Result<Filter, FilterError> {
+    let operators = match condition {
+        Value::Object(obj) if obj.keys().any(|key| key.starts_with('$')) => obj,
+        _ => return Ok(Filter::new(field, Operator::Equals(condition.clone()))),
+    };
+
+    let mut filters = Vec::with_capacity(operators.len());
+    for (key, operand) in operators {
+        let number = || {
+            operand.as_f64().ok_or_else(|| {
+                FilterError::Unsupported(format!("{} with non-numeric operand {:?}", key, operand))
+            })
+        };
+
+        let operator = match key.as_str() {
+            "$eq" => Operator::Equals(operand.clone()),
+            "$ne" => Operator::NotEqual(operand.clone()),
+            "$gt" => Operator::GreaterThan(number()?),
+            "$gte" => Operator::GreaterOrEqual(number()?),
+            "$lt" => Operator::LessThan(number()?),
+            "$lte" => Operator::LessOrEqual(number()?),
+            "$in" => match operand {
+                Value::Array(candidates) => Operator::In(candidates.clone()),
+                _ => {
+                    return Err(FilterError::Unsupported(format!(
+                        "$in expects an array, got {:?}",
+                        operand
+                    )))
+                }
+            },
+            key => return Err(FilterError::Unsupported(key.to_string())),
+        };
+        filters.push(Filter::new(".", operator));
+    }
+
+    Ok(Filter::new(field, single_or_and(".", filters).operator))
+}
+
+fn single_or_and(path: &str, mut filters: Vec<Filter>) -> Filter {
+    if filters.len() == 1 {
+        filters.remove(0)
+    } else {
+        Filter::new(path, Operator::And(filters))
+    }
+}
+
+fn to_mongo(filter: &Filter, parent: &str) -> Result<Value, FilterError> {
+    let path = nested_path(parent, &filter.path);
+
+    let (key, operand) = match &filter.operator {
+        Operator::And(filters) => {
+            let queries = filters
+                .iter()
+                .map(|filter| to_mongo(filter, &path))
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(merge_queries(queries));
+        }
+        Operator::Or(filters) => {
+            let queries = filters
+                .iter()
+                .map(|filter| to_mongo(filter, &path))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut query = Map::new();
+            query.insert("$or".to_string(), Value::Array(queries));
+            return Ok(Value::Object(query));
+        }
+        Operator::Equals(value) if !value.is_object() => {
+            return field_query(&path, value.clone());
+        }
+        Operator::Equals(value) => ("$eq", value.clone()),
+        Operator::NotEqual(value) => ("$ne", value.clone()),
+        Operator::GreaterThan(n) => ("$gt", Value::from(*n)),
+        Operator::GreaterOrEqual(n) => ("$gte", Value::from(*n)),
+        Operator::LessThan(n) => ("$lt", Value::from(*n)),
+        Operator::LessOrEqual(n) => ("$lte", Value::from(*n)),
+        Operator::In(candidates) => ("$in", Value::Array(candidates.clone())),
+        operator => {
+            return Err(FilterError::Unsupported(format!(
+                "{:?} has no MongoDB equivalent",
+                operator
+            )))
+        }
+    };
+
+    let mut condition = Map::new();
+    condition.insert(key.to_string(), operand);
+    field_query(&path, Value::Object(condition))
+}
+
+fn field_query(path: &str, condition: Value) -> Result<Value, FilterError> {
+    if path == "." {
+        return Err(FilterError::Unsupported(
+            "MongoDB conditions need a field path".to_string(),
+        ));
+    }
+    let mut query = Map::new();
+    query.insert(path.to_string(), condition);
+    Ok(Value::Object(query))
+}
+
+/// Combines queries into a single document when their fields don't clash (so
+/// `age > 1 && age < 5` becomes `{ "age": { "$gt": 1, "$lt": 5 } }`), falling
+/// back to an explicit `$and`.
+fn merge_queries(queries: Vec<Value>) -> Value {
+    let mut merged = Map::new();
+
+    for query in &queries {
+        let Value::Object(query) = query else {
+            unreachable!("to_mongo always produces objects");
+        };
+        for (key, condition) in query {
+            match (merged.get_mut(key), condition) {
+                (None, condition) => {
+                    merged.insert(key.clone(), condition.clone());
+                }
+                (Some(Value::Object(existing)), Value::Object(condition))
+                    if is_operator_map(existing)
+                        && is_operator_map(condition)
+                        && condition.keys().all(|key| !existing.contains_key(key)) =>
+                {
+                    existing.extend(condition.clone());
+                }
+                _ => {
+                    let mut query = Map::new();
+                    query.insert("$and".to_string(), Value::Array(queries));
+                    return Value::Object(query);
+                }
+            }
+        }
+    }
+
+    Value::Object(merged)
+}
+
+fn is_operator_map(obj: &Map<String, Value>) -> bool {
+    obj.keys().all(|key| key.starts_with('$'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_from_mongo_query() {
+        let query = json!({
+            "age": { "$gt": 20, "$lt": 65 },
+            "status": { "$in": ["active", "pending"] },
+            "name": "John",
+            "$or": [{ "role": "admin" }, { "score": { "$gte": 90 } }]
+        });
+
+        let filter = Filter::from_mongo_query(&query).unwrap();
+        let expected = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new(
+                    ".",
+                    Operator::Or(vec![
+                        Filter::new("role", Operator::Equals(json!("admin"))),
+                        Filter::new("score", Operator::GreaterOrEqual(90.0)),
+                    ]),
+                ),
+                Filter::new(
+                    "age",
+                    Operator::And(vec![
+                        Filter::new(".", Operator::GreaterThan(20.0)),
+                        Filter::new(".", Operator::LessThan(65.0)),
+                    ]),
+                ),
+                Filter::new("name", Operator::Equals(json!("John"))),
+                Filter::new(
+                    "status",
+                    Operator::In(vec![json!("active"), json!("pending")]),
+                ),
+            ]),
+        );
+        assert_eq!(filter, expected);
+
+        let value =
+            json!({ "age": 30, "status": "active", "name": "John", "score": 95, "role": "x" });
+        assert!(filter.check(&value).unwrap());
+        let value =
+            json!({ "age": 30, "status": "active", "name": "John", "score": 10, "role": "x" });
+        assert!(!filter.check(&value).unwrap());
+    }
+
+    #[test]
+    fn test_mongo_round_trip() {
+        let queries = [
+            json!({ "age": { "$gt": 20.0 } }),
+            json!({ "age": { "$gte": 20.0, "$lte": 30.0 }, "name": "John" }),
+            json!({ "status": { "$in": ["a", 1] }, "role": { "$ne": "guest" } }),
+            json!({ "$or": [{ "a": 1 }, { "b": { "$lt": 2.0 } }] }),
+            json!({ "$and": [{ "a": 1 }, { "a": 2 }] }),
+            json!({ "meta": { "$eq": { "nested": true } } }),
+        ];
+
+        for query in queries {
+            let filter = Filter::from_mongo_query(&query).unwrap();
+            assert_eq!(filter.to_mongo_query().unwrap(), query);
+            assert_eq!(
+                Filter::from_mongo_query(&filter.to_mongo_query().unwrap()).unwrap(),
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn test_mongo_unsupported() {
+        for query in [
+            json!({ "name": { "$regex": "^J" } }),
+            json!({ "$where": "this.a > 1" }),
+            json!({ "age": { "$gt": "20" } }),
+            json!(["not", "an", "object"]),
+        ] {
+            assert!(matches!(
+                Filter::from_mongo_query(&query),
+                Err(FilterError::Unsupported(..))
+            ));
+        }
+
+        let filter = Filter::new("name", Operator::StartsWith("J".to_string()));
+        assert!(matches!(
+            filter.to_mongo_query(),
+            Err(FilterError::Unsupported(..))
+        ));
+    }
+}
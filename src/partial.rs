@@ -0,0 +1,173 @@
+//! Filter templates with named holes, bound to concrete operands later.
+//!
+//! A template is the JSON form of a [`Filter`] in which any operand may be
+//! replaced by `{ "$hole": "name" }`. Binding every hole and deserializing the
+//! result yields an ordinary filter, much like a prepared statement.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::Value;
+
+use crate::{Filter, FilterError};
+
+/// A [`Filter`] whose operands may be left unbound, see the
+/// [module documentation](self).
+///
+/// ```
+/// use json_filter::{Filter, Operator, PartialFilter};
+/// use serde_json::json;
+///
+/// let template = PartialFilter::new(json!({
+///     "path": "age",
+///     "operator": { "GreaterThan": { "$hole": "min_age" } }
+/// }));
+/// assert_eq!(
+///     template.bind("min_age", 18).unwrap(),
+///     Filter::new("age", Operator::GreaterThan(18.0))
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialFilter {
+    template: Value,
+    holes: BTreeSet<String>,
+}
+
+impl PartialFilter {
+    pub fn new(template: Value) -> Self {
+        let mut holes = BTreeSet::new();
+        collect_holes(&template, &mut holes);
+        Self { template, holes }
+    }
+
+    /// Names of the holes in this template, in sorted order.
+    pub fn holes(&self) -> impl Iterator<Item = &str> {
+        self.holes.iter().map(String::as_str)
+    }
+
+    /// Fills the hole `name` with `value` and returns the resulting filter.
+    ///
+    /// Fails if the template has no such hole or has other holes left, see
+    /// [`PartialFilter::bind_all`] for templates with several of them.
+    pub fn bind(&self, name: &str, value: impl Into<Value>) -> Result<Filter, FilterError> {
+        self.bind_all([(name, value.into())])
+    }
+
+    /// Fills every hole from `values` and returns the resulting filter, which
+    /// is [validated](Filter::validate) like one built by [`Filter::try_new`].
+    ///
+    /// Fails if a name isn't a hole of this template, a hole is left unbound,
+    /// or a value has the wrong type for the operand it replaces.
+    pub fn bind_all<'a>(
+        &self,
+        values: impl IntoIterator<Item = (&'a str, Value)>,
+    ) -> Result<Filter, FilterError> {
+        let values = values.into_iter().collect::<BTreeMap<_, _>>();
+        if let Some(name) = values.keys().find(|name| !self.holes.contains(**name)) {
+            return Err(FilterError::InvalidOperand(format!(
+                "unknown hole `{}`",
+                name
+            )));
+        }
+        if let Some(name) = self.holes().find(|name| !values.contains_key(name)) {
+            return Err(FilterError::InvalidOperand(format!(
+                "unbound hole `{}`",
+                name
+            )));
+        }
+
+        let mut template = self.template.clone();
+        fill_holes(&mut template, &values);
+        let filter: Filter = serde_json::from_value(template)
+            .map_err(|error| FilterError::InvalidOperand(error.to_string()))?;
+        filter.validate()?;
+        Ok(filter)
+    }
+}
+
+fn hole_name(value: &Value) -> Option<&str> {
+    match value {
+        Value::Object(map) if map.len() == 1 => map.get("$hole")?.as_str(),
+        _ => None,
+    }
+}
+
+fn collect_holes(value: &Value, holes: &mut BTreeSet<String>) {
+    if let Some(name) = hole_name(value) {
+        holes.insert(name.to_string());
+        return;
+    }
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| collect_holes(item, holes)),
+        Value::Object(map) => map.values().for_each(|item| collect_holes(item, holes)),
+        _ => {}
+    }
+}
+
+fn fill_holes(value: &mut Value, values: &BTreeMap<&str, Value>) {
+    if let Some(bound) = hole_name(value).and_then(|name| values.get(name)) {
+        *value = bound.clone();
+        return;
+    }
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(|item| fill_holes(item, values)),
+        Value::Object(map) => map.values_mut().for_each(|item| fill_holes(item, values)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Operator;
+    use serde_json::json;
+
+    #[test]
+    fn test_partial_filter() {
+        let template = PartialFilter::new(json!({
+            "path": "status",
+            "operator": { "Equals": { "$hole": "status" } }
+        }));
+        assert_eq!(template.holes().collect::<Vec<_>>(), vec!["status"]);
+
+        let active = template.bind("status", "active").unwrap();
+        assert_eq!(
+            active,
+            Filter::new("status", Operator::Equals(json!("active")))
+        );
+        let archived = template.bind("status", "archived").unwrap();
+        assert!(archived.check(&json!({ "status": "archived" })).unwrap());
+        assert!(!active.check(&json!({ "status": "archived" })).unwrap());
+
+        assert!(matches!(
+            template.bind("state", "active"),
+            Err(FilterError::InvalidOperand(..))
+        ));
+    }
+
+    #[test]
+    fn test_partial_filter_several_holes() {
+        let template = PartialFilter::new(json!({
+            "path": ".",
+            "operator": { "And": [
+                { "path": "age", "operator": { "GreaterOrEqual": { "$hole": "min" } } },
+                { "path": "age", "operator": { "LessThan": { "$hole": "max" } } }
+            ] }
+        }));
+        assert_eq!(template.holes().collect::<Vec<_>>(), vec!["max", "min"]);
+
+        let filter = template
+            .bind_all([("min", json!(18)), ("max", json!(65))])
+            .unwrap();
+        assert!(filter.check(&json!({ "age": 30 })).unwrap());
+        assert!(!filter.check(&json!({ "age": 70 })).unwrap());
+
+        assert!(matches!(
+            template.bind("min", 18),
+            Err(FilterError::InvalidOperand(message)) if message == "unbound hole `max`"
+        ));
+        assert!(matches!(
+            template.bind_all([("min", json!("young")), ("max", json!(65))]),
+            Err(FilterError::InvalidOperand(..))
+        ));
+    }
+}
@@ -0,0 +1,478 @@
+//! Tokenizer for filter paths such as `orders[?status=open].items.*.sku#keys`.
+//!
+//! A path is either `.` (the value itself) or a `.`-separated list of segments,
+//! optionally followed by a `#name` computed suffix. Each segment is a key, `*`,
+//! or an optional key followed by `[...]` selectors: an index (negative ones
+//! count from the end), `[]`, `[key=value]`, `[?key=value]` or a quoted key
+//! such as `["a.b"]` (a JSON string literal, for keys containing `.`, brackets
+//! and the like). With a quoted key, `[key=value]` and `[?key=value]` also
+//! accept `!=`, `<`, `<=`, `>` and `>=` in place of `=`, e.g. `[?"age">=18]`;
+//! unquoted keys extend to the first `=` as they always have, so `[a<b=1]` is
+//! the key `a<b`. Anything else is rejected with [`FilterError::InvalidPath`].
+
+use std::borrow::Cow;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::{CompareOp, FilterError};
+
+/// A single step of a parsed path.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PathSegment {
+    /// An object key.
+    Key(String),
+    /// `*`: every element of an array or every value of an object.
+    Wildcard,
+    /// `[n]`: the `n`th element of an array.
+    Index(usize),
+    /// `[-n]`: the `n`th element from the end of an array, so `[-1]` is the last.
+    IndexFromEnd(usize),
+    /// `[]`: every element of an array.
+    AllElements,
+    /// `[key=value]` (first element whose `key` equals `value`) or
+    /// `[?key=value]` (every such element). The other comparisons order
+    /// numbers and strings, and never select elements of other types.
+    Where {
+        key: String,
+        op: CompareOp,
+        value: Value,
+        all: bool,
+    },
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Key(key) => f.write_str(&quote_key(key)),
+            PathSegment::Wildcard => f.write_str("*"),
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+            PathSegment::IndexFromEnd(index) => write!(f, "[-{}]", index),
+            PathSegment::AllElements => f.write_str("[]"),
+            PathSegment::Where {
+                key,
+                op,
+                value,
+                all,
+            } => {
+                let marker = if *all { "?" } else { "" };
+                // The unquoted form only for what it parses back into
+                let plain = *op == CompareOp::Eq
+                    && !key.is_empty()
+                    && !key.starts_with(['"', '?'])
+                    && !key.contains(['=', '[', ']'])
+                    && match value {
+                        Value::String(s) => parse_literal(s) == *value && !s.contains(']'),
+                        _ => true,
+                    };
+                match value {
+                    _ if !plain => write!(
+                        f,
+                        "[{}{}{}{}]",
+                        marker,
+                        Value::from(key.as_str()),
+                        op_symbol(*op),
+                        value
+                    ),
+                    Value::String(s) => write!(f, "[{}{}={}]", marker, key, s),
+                    value => write!(f, "[{}{}={}]", marker, key, value),
+                }
+            }
+        }
+    }
+}
+
+/// The key as written in a path: as is when it's a plain key, otherwise as a
+/// quoted `["..."]` selector.
+pub(crate) fn quote_key(key: &str) -> Cow<'_, str> {
+    if key.is_empty() || key == "*" || key.contains(['.', '[', ']', '#', '"']) {
+        Cow::Owned(format!("[{}]", Value::from(key)))
+    } else {
+        Cow::Borrowed(key)
+    }
+}
+
+/// The comparison as written in a `[key=value]` selector.
+pub(crate) fn op_symbol(op: CompareOp) -> &'static str {
+    match op {
+        CompareOp::Eq => "=",
+        CompareOp::Ne => "!=",
+        CompareOp::Gt => ">",
+        CompareOp::Ge => ">=",
+        CompareOp::Lt => "<",
+        CompareOp::Le => "<=",
+    }
+}
+
+/// A path split into its segments and optional `#name` suffix.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ParsedPath {
+    pub(crate) segments: Vec<PathSegment>,
+    pub(crate) computed: Option<String>,
+}
+
+pub(crate) fn parse_path(path: &str) -> Result<ParsedPath, FilterError> {
+    let invalid = || FilterError::InvalidPath(path.to_string());
+
+    let mut segments = Vec::new();
+    let mut pos = 0;
+
+    if path == "." {
+        return Ok(ParsedPath {
+            segments,
+            computed: None,
+        });
+    }
+
+    // `#keys` and `.#keys` apply the suffix to the value itself
+    if path.starts_with('#') || path.starts_with(".#") {
+        pos = path.find('#').unwrap_or_default();
+    } else {
+        loop {
+            let key_end = path[pos..]
+                .find(['.', '[', ']', '#'])
+                .map_or(path.len(), |i| pos + i);
+            let key = &path[pos..key_end];
+            pos = key_end;
+
+            let mut selectors = Vec::new();
+            while path[pos..].starts_with('[') {
+                // A quoted key may contain `]`, so the search starts after it
+                let key_start = pos + 1 + usize::from(path[pos + 1..].starts_with("?\""));
+                let from = if path[key_start..].starts_with('"') {
+                    closing_quote(path, key_start).map_or(pos + 1, |quote| quote + 1)
+                } else {
+                    pos + 1
+                };
+                let close = path[from..].find(']').ok_or_else(invalid)? + from;
+                selectors.push(parse_selector(&path[pos + 1..close]).ok_or_else(invalid)?);
+                pos = close + 1;
+            }
+
+            match key {
+                "" if selectors.is_empty() => return Err(invalid()),
+                "" => {}
+                "*" => segments.push(PathSegment::Wildcard),
+                key => segments.push(PathSegment::Key(key.to_string())),
+            }
+            segments.extend(selectors);
+
+            match path[pos..].chars().next() {
+                None | Some('#') => break,
+                Some('.') => pos += 1,
+                Some(_) => return Err(invalid()),
+            }
+        }
+    }
+
+    let computed = match path[pos..].strip_prefix('#') {
+        Some(name) => Some(parse_computed(name).ok_or_else(invalid)?),
+        None => None,
+    };
+
+    Ok(ParsedPath { segments, computed })
+}
+
+/// The index of the `"` closing the string literal opened at `start`.
+fn closing_quote(path: &str, start: usize) -> Option<usize> {
+    let mut escaped = false;
+    for (i, b) in path.bytes().enumerate().skip(start + 1) {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_selector(selector: &str) -> Option<PathSegment> {
+    let (body, all) = match selector.strip_prefix('?') {
+        Some(body) => (body, true),
+        None => (selector, false),
+    };
+    if selector.starts_with('"') {
+        return serde_json::from_str(selector)
+            .ok()
+            .map(PathSegment::Key)
+            .or_else(|| parse_quoted_comparison(body, all));
+    }
+    if all && body.starts_with('"') {
+        // Otherwise parsed as before quoted comparisons, e.g. `[?"a=1]` as the key `"a`
+        if let Some(segment) = parse_quoted_comparison(body, all) {
+            return Some(segment);
+        }
+    }
+    if selector.contains('[') {
+        return None;
+    }
+
+    if let Some((key, literal)) = body.split_once('=') {
+        if key.is_empty() {
+            return None;
+        }
+        return Some(PathSegment::Where {
+            key: key.to_string(),
+            op: CompareOp::Eq,
+            value: parse_literal(literal),
+            all,
+        });
+    }
+
+    if selector.is_empty() {
+        return Some(PathSegment::AllElements);
+    }
+    let (digits, from_end) = match selector.strip_prefix('-') {
+        Some(digits) => (digits, true),
+        None => (selector, false),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    match digits.parse().ok()? {
+        0 if from_end => None,
+        index if from_end => Some(PathSegment::IndexFromEnd(index)),
+        index => Some(PathSegment::Index(index)),
+    }
+}
+
+/// Parses `"key" op literal`, the key being a JSON string literal.
+fn parse_quoted_comparison(body: &str, all: bool) -> Option<PathSegment> {
+    let mut keys = serde_json::Deserializer::from_str(body).into_iter::<String>();
+    let key = keys.next()?.ok()?;
+    let rest = &body[keys.byte_offset()..];
+    if rest.contains('[') {
+        return None;
+    }
+    let (op, literal) = [
+        CompareOp::Ne,
+        CompareOp::Le,
+        CompareOp::Ge,
+        CompareOp::Eq,
+        CompareOp::Lt,
+        CompareOp::Gt,
+    ]
+    .into_iter()
+    .find_map(|op| Some((op, rest.strip_prefix(op_symbol(op))?)))?;
+
+    Some(PathSegment::Where {
+        key,
+        op,
+        value: parse_literal(literal),
+        all,
+    })
+}
+
+/// `[id=3]` compares against the number 3, `[status=active]` against the string.
+fn parse_literal(literal: &str) -> Value {
+    serde_json::from_str(literal).unwrap_or_else(|_| Value::String(literal.to_string()))
+}
+
+fn parse_computed(name: &str) -> Option<String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_path() {
+        let parsed = parse_path("orders[?status=open].items.*[0].sku#keys").unwrap();
+        assert_eq!(
+            parsed.segments,
+            vec![
+                PathSegment::Key("orders".to_string()),
+                PathSegment::Where {
+                    key: "status".to_string(),
+                    op: CompareOp::Eq,
+                    value: json!("open"),
+                    all: true,
+                },
+                PathSegment::Key("items".to_string()),
+                PathSegment::Wildcard,
+                PathSegment::Index(0),
+                PathSegment::Key("sku".to_string()),
+            ]
+        );
+        assert_eq!(parsed.computed.as_deref(), Some("keys"));
+
+        assert_eq!(parse_path(".").unwrap().segments, vec![]);
+        assert_eq!(
+            parse_path("#keys").unwrap().computed.as_deref(),
+            Some("keys")
+        );
+        assert_eq!(
+            parse_path(".#keys").unwrap().computed.as_deref(),
+            Some("keys")
+        );
+        assert_eq!(
+            parse_path("[1]").unwrap().segments,
+            vec![PathSegment::Index(1)]
+        );
+
+        assert_eq!(
+            parse_path("items[-2]").unwrap().segments,
+            vec![
+                PathSegment::Key("items".to_string()),
+                PathSegment::IndexFromEnd(2),
+            ]
+        );
+
+        assert_eq!(
+            parse_path("matrix[1][-1][0]").unwrap().segments,
+            vec![
+                PathSegment::Key("matrix".to_string()),
+                PathSegment::Index(1),
+                PathSegment::IndexFromEnd(1),
+                PathSegment::Index(0),
+            ]
+        );
+
+        assert_eq!(
+            parse_path("items[].price").unwrap().segments,
+            vec![
+                PathSegment::Key("items".to_string()),
+                PathSegment::AllElements,
+                PathSegment::Key("price".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            parse_path(r#"["a.b"].c["x]\"y"]"#).unwrap().segments,
+            vec![
+                PathSegment::Key("a.b".to_string()),
+                PathSegment::Key("c".to_string()),
+                PathSegment::Key("x]\"y".to_string()),
+            ]
+        );
+
+        // Dots inside a selector belong to the literal
+        assert_eq!(
+            parse_path("releases[version=1.2]").unwrap().segments[1],
+            PathSegment::Where {
+                key: "version".to_string(),
+                op: CompareOp::Eq,
+                value: json!(1.2),
+                all: false,
+            }
+        );
+
+        // Comparisons other than `=` need a quoted key
+        let parsed = parse_path(r#"users[?"age">=18]"#).unwrap();
+        assert_eq!(
+            parsed.segments[1],
+            PathSegment::Where {
+                key: "age".to_string(),
+                op: CompareOp::Ge,
+                value: json!(18),
+                all: true,
+            }
+        );
+        assert_eq!(parsed.segments[1].to_string(), r#"[?"age">=18]"#);
+        assert_eq!(
+            parse_path(r#"users["a]b"!="x"]"#).unwrap().segments[1],
+            PathSegment::Where {
+                key: "a]b".to_string(),
+                op: CompareOp::Ne,
+                value: json!("x"),
+                all: false,
+            }
+        );
+
+        // Unquoted keys still run up to the first `=`
+        for (path, key, value) in [
+            ("a[b<c=1]", "b<c", json!(1)),
+            ("a[?age>=18]", "age>", json!(18)),
+            ("a[name!=a=b]", "name!", json!("a=b")),
+            (r#"a[?"b=1]"#, "\"b", json!(1)),
+        ] {
+            let parsed = parse_path(path).unwrap();
+            assert_eq!(
+                parsed.segments[1],
+                PathSegment::Where {
+                    key: key.to_string(),
+                    op: CompareOp::Eq,
+                    value,
+                    all: path.contains('?'),
+                }
+            );
+            let written = format!("a{}", parsed.segments[1]);
+            assert_eq!(parse_path(&written).unwrap(), parsed);
+        }
+    }
+
+    #[test]
+    fn test_parse_path_malformed() {
+        for path in [
+            "",
+            "..",
+            ".a",
+            "a.",
+            "a..b",
+            "a[0",
+            "a]",
+            "a[0]]",
+            "a[[0]]",
+            "a[0]b",
+            "a[0][1]b",
+            "a[0][",
+            r#"a["b]"#,
+            r#"a["b"x]"#,
+            r#"a["b\"]"#,
+            "a[-0]",
+            "a[-]",
+            "a[--1]",
+            "a[+1]",
+            "a[x]",
+            "a[=1]",
+            "a[?=1]",
+            "a[b<1]",
+            r#"a[?"b"]"#,
+            r#"a["b"~1]"#,
+            "a[99999999999999999999999]",
+            "a#",
+            "a#b.c",
+            "a.#keys",
+            "#",
+        ] {
+            assert!(
+                matches!(parse_path(path), Err(FilterError::InvalidPath(..))),
+                "{:?} should be rejected",
+                path
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_path_never_panics(path in "\\PC*") {
+            match parse_path(&path) {
+                Ok(_) | Err(FilterError::InvalidPath(_)) => {}
+                Err(error) => prop_assert!(false, "unexpected error {:?}", error),
+            }
+        }
+
+        #[test]
+        fn test_parse_path_structured(path in "[a-z.*#\\[\\]?=0-9]{0,16}") {
+            match parse_path(&path) {
+                Ok(parsed) => {
+                    for segment in &parsed.segments {
+                        if let PathSegment::Key(key) = segment {
+                            prop_assert!(!key.is_empty());
+                            prop_assert!(!key.contains(['.', '[', ']', '#']));
+                        }
+                    }
+                }
+                Err(FilterError::InvalidPath(_)) => {}
+                Err(error) => prop_assert!(false, "unexpected error {:?}", error),
+            }
+        }
+    }
+}
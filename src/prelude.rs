@@ -0,0 +1,116 @@
+//! Terse free functions for building filters without a macro or builder.
+//!
+//! ```
+//! use json_filter::prelude::*;
+//! use serde_json::json;
+//!
+//! let filter = all(vec![gt("age", 18.0), any(vec![eq("role", json!("admin")), contains("email", "@corp.")])]);
+//! assert!(filter.check(&json!({ "age": 30, "role": "admin", "email": "x@y.z" })).unwrap());
+//! ```
+
+use serde_json::Value;
+
+pub use crate::{Filter, FilterError, Operator};
+
+pub fn gt(path: impl Into<String>, n: f64) -> Filter {
+    Filter::new(path, Operator::GreaterThan(n))
+}
+
+pub fn lt(path: impl Into<String>, n: f64) -> Filter {
+    Filter::new(path, Operator::LessThan(n))
+}
+
+pub fn ge(path: impl Into<String>, n: f64) -> Filter {
+    Filter::new(path, Operator::GreaterOrEqual(n))
+}
+
+pub fn le(path: impl Into<String>, n: f64) -> Filter {
+    Filter::new(path, Operator::LessOrEqual(n))
+}
+
+pub fn eq(path: impl Into<String>, value: Value) -> Filter {
+    Filter::new(path, Operator::Equals(value))
+}
+
+pub fn ne(path: impl Into<String>, value: Value) -> Filter {
+    Filter::new(path, Operator::NotEqual(value))
+}
+
+pub fn one_of(path: impl Into<String>, values: Vec<Value>) -> Filter {
+    Filter::new(path, Operator::In(values))
+}
+
+pub fn starts_with(path: impl Into<String>, s: impl Into<String>) -> Filter {
+    Filter::new(path, Operator::StartsWith(s.into()))
+}
+
+pub fn ends_with(path: impl Into<String>, s: impl Into<String>) -> Filter {
+    Filter::new(path, Operator::EndsWith(s.into()))
+}
+
+pub fn contains(path: impl Into<String>, s: impl Into<String>) -> Filter {
+    Filter::new(path, Operator::Contains(s.into()))
+}
+
+pub fn array_contains(path: impl Into<String>, value: Value) -> Filter {
+    Filter::new(path, Operator::ArrayContains(value))
+}
+
+pub fn has_key(path: impl Into<String>, key: impl Into<String>) -> Filter {
+    Filter::new(path, Operator::HasKey(key.into()))
+}
+
+/// Matches when every filter matches, with paths relative to the root.
+pub fn all(filters: Vec<Filter>) -> Filter {
+    Filter::new(".", Operator::And(filters))
+}
+
+/// Matches when at least one filter matches, with paths relative to the root.
+pub fn any(filters: Vec<Filter>) -> Filter {
+    Filter::new(".", Operator::Or(filters))
+}
+
+/// Matches when `filter` doesn't, with its path relative to the root.
+pub fn not(filter: Filter) -> Filter {
+    Filter::new(".", Operator::Not(Box::new(filter)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_prelude_functions() {
+        let filter = all(vec![
+            gt("age", 20.0),
+            any(vec![
+                starts_with("name", "John"),
+                one_of("role", vec![json!("admin"), json!("owner")]),
+            ]),
+            array_contains("tags", json!("rust")),
+        ]);
+
+        let expected = Filter::new(
+            ".",
+            Operator::And(vec![
+                Filter::new("age", Operator::GreaterThan(20.0)),
+                Filter::new(
+                    ".",
+                    Operator::Or(vec![
+                        Filter::new("name", Operator::StartsWith("John".to_string())),
+                        Filter::new("role", Operator::In(vec![json!("admin"), json!("owner")])),
+                    ]),
+                ),
+                Filter::new("tags", Operator::ArrayContains(json!("rust"))),
+            ]),
+        );
+        assert_eq!(filter, expected);
+
+        let value = json!({ "age": 25, "name": "Jane", "role": "owner", "tags": ["rust"] });
+        assert!(filter.check(&value).unwrap());
+        let value = json!({ "age": 25, "name": "Jane", "role": "guest", "tags": ["rust"] });
+        assert!(!filter.check(&value).unwrap());
+        assert!(not(filter).check(&value).unwrap());
+    }
+}
@@ -0,0 +1,81 @@
+//! Assertions for tests of code using filters, enabled by the `testing` feature.
+//!
+//! On failure they panic with the evaluation trace from
+//! [`Filter::explain_json`], which shows the branch and operator that decided
+//! the result.
+
+use serde_json::Value;
+
+use crate::Filter;
+
+/// Panics unless `filter` matches `value`, also if the check fails.
+#[track_caller]
+pub fn assert_matches(filter: &Filter, value: &Value) {
+    assert_result(filter, value, true);
+}
+
+/// Panics unless `filter` doesn't match `value`, also if the check fails.
+#[track_caller]
+pub fn assert_not_matches(filter: &Filter, value: &Value) {
+    assert_result(filter, value, false);
+}
+
+#[track_caller]
+fn assert_result(filter: &Filter, value: &Value, expected: bool) {
+    let outcome = match filter.check(value) {
+        Ok(result) if result == expected => return,
+        Ok(true) => "matched".to_string(),
+        Ok(false) => "didn't match".to_string(),
+        Err(error) => format!("failed ({})", error),
+    };
+    let trace = serde_json::to_string_pretty(&filter.explain_json(value))
+        .unwrap_or_else(|error| error.to_string());
+    panic!(
+        "filter {} but was expected to {}\nvalue: {}\ntrace: {}",
+        outcome,
+        if expected { "match" } else { "not match" },
+        value,
+        trace
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Operator;
+    use serde_json::json;
+
+    fn adult() -> Filter {
+        Filter::new("age", Operator::GreaterOrEqual(18.0))
+    }
+
+    #[test]
+    fn test_assertions_pass() {
+        assert_matches(&adult(), &json!({ "age": 30 }));
+        assert_not_matches(&adult(), &json!({ "age": 12 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "filter didn't match but was expected to match\nvalue: {\"age\":12}")]
+    fn test_assert_matches_message() {
+        assert_matches(&adult(), &json!({ "age": 12 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "\"operator\": \"GreaterOrEqual\"")]
+    fn test_assert_matches_trace() {
+        assert_matches(&adult(), &json!({ "age": 12 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "filter matched but was expected to not match")]
+    fn test_assert_not_matches_message() {
+        assert_not_matches(&adult(), &json!({ "age": 30 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "filter failed (Path not found: age)")]
+    fn test_assert_not_matches_error() {
+        assert_not_matches(&adult(), &json!({}));
+    }
+}